@@ -1,14 +1,15 @@
 use std::collections::HashSet;
 
 pub use crate::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Render {
     pub color: ColorPair,
     pub glyph: FontCharType,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Player {
     pub map_level: usize,
 }
@@ -19,25 +20,38 @@ impl Player {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Enemy;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct MovingRandomly;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToMove {
     pub entity: Entity,
     pub destination: Point,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Stands in for the `Point` of an entity left behind on another level.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredOnLevel {
+    pub level: usize,
+    pub position: Point,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct WantsToCloseDoor {
+    pub entity: Entity,
+    pub position: Point,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToActivateItem {
     pub used_by: Entity,
     pub item: Entity,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Health {
     pub current: i32,
     pub max: i32,
@@ -52,23 +66,49 @@ impl Health {
     }
 }
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Name(pub String);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct WantsToAttack {
     pub attacker: Entity,
     pub victim: Entity,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct ChasingPlayer;
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+/// Where a chasing monster last saw the player. Once it gets there it searches around for
+/// `search_turns` more turns before giving up.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LastSeenPlayer {
+    pub pos: Point,
+    pub search_turns: i32,
+}
+
+/// Stays where it was put, and only attacks the player when they come next to it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Guarding;
+
+/// Fights a player it can see, but never goes looking for one it's lost track of.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Cowardly;
+
+/// Runs from the player while its health is below this, whatever its AI would otherwise do.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct FleesBelow(pub i32);
+
+/// Keeps still until the player is next to it, then attacks and turns into a `ChasingPlayer`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LyingInWait;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Item;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct AmuletOfYala;
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct FieldOfView {
     pub visible_tiles: HashSet<Point>,
     pub radius: i32,
@@ -93,19 +133,163 @@ impl FieldOfView {
     }
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Carried(pub Entity);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct ProvidesHealing {
+/// One of the effects in the `EFFECTS` registry, with the number its template gave it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ProvidedEffect {
+    pub effect: String,
     pub amount: i32,
 }
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct ProvidesDungeonMap;
+impl ProvidedEffect {
+    pub fn new(effect: &str, amount: i32) -> Self {
+        Self {
+            effect: effect.to_string(),
+            amount,
+        }
+    }
+}
+
+/// What an item does when it's used, in order.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Provides(pub Vec<ProvidedEffect>);
+
+/// How many tiles around its target an item's effect reaches.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AreaOfEffect(pub i32);
+
+/// Added alongside `WantsToActivateItem` when the item was aimed at `0` from targeting mode.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct TargetedAt(pub Point);
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Damage(pub Dice);
+
+/// Added to the d20 roll that decides whether an attack lands.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Accuracy(pub i32);
+
+/// Raises the roll an attacker needs to land a hit.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Evasion(pub i32);
+
+/// An item that can be fired or thrown at a target up to `range` tiles away. Items without
+/// `Damage`, like scrolls, are aimed the same way and put their effects on the target.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ranged {
+    pub range: i32,
+}
+
+/// How many more shots a ranged item has. Throwables are used up when it runs out.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Ammo(pub i32);
+
+/// Added alongside `WantsToAttack` when the attack is a shot from `weapon`, whose damage replaces
+/// the attacker's melee damage.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RangedAttack {
+    pub weapon: Entity,
+}
+
+/// Defence against incoming blows, on a monster's hide or an item of gear.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Armor(pub i32);
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Damage(pub i32);
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    MainHand,
+    OffHand,
+    Body,
+    Ring,
+    /// Bows and the like, which are fired from targeting mode rather than swung.
+    Ranged,
+}
+
+impl EquipmentSlot {
+    pub fn name(self) -> &'static str {
+        match self {
+            EquipmentSlot::MainHand => "main hand",
+            EquipmentSlot::OffHand => "off hand",
+            EquipmentSlot::Body => "body",
+            EquipmentSlot::Ring => "ring",
+            EquipmentSlot::Ranged => "ranged",
+        }
+    }
+}
+
+/// An item that can be worn or wielded in `slot` rather than used up.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equippable(pub EquipmentSlot);
+
+/// Marks a `Carried` item as being worn or wielded by its owner, so its bonuses count.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Equipped(pub EquipmentSlot);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum StatusKind {
+    /// Loses a hit point every turn.
+    Poison,
+    /// Gains a hit point every turn.
+    Regeneration,
+    /// Stumbles off in a random direction whenever it tries to move.
+    Confusion,
+    /// Only gets to act every other turn.
+    Slow,
+}
+
+impl StatusKind {
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusKind::Poison => "poisoned",
+            StatusKind::Regeneration => "regenerating",
+            StatusKind::Confusion => "confused",
+            StatusKind::Slow => "slowed",
+        }
+    }
+
+    /// Drawn in the HUD with the number of turns left.
+    pub fn icon(self) -> (char, (u8, u8, u8)) {
+        match self {
+            StatusKind::Poison => ('♣', GREEN),
+            StatusKind::Regeneration => ('♥', RED),
+            StatusKind::Confusion => ('?', MAGENTA),
+            StatusKind::Slow => ('~', CYAN),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub turns: i32,
+}
+
+impl StatusEffect {
+    pub fn new(kind: StatusKind, turns: i32) -> Self {
+        Self { kind, turns }
+    }
+}
+
+/// The timed effects an entity is under, ticked down by the `status_effects` system.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatusEffects(pub Vec<StatusEffect>);
+
+impl StatusEffects {
+    /// Applying an effect the entity already has tops it back up rather than stacking it.
+    pub fn add(&mut self, effect: StatusEffect) {
+        match self.0.iter_mut().find(|active| active.kind == effect.kind) {
+            Some(active) => active.turns = active.turns.max(effect.turns),
+            None => self.0.push(effect),
+        }
+    }
+
+    pub fn has(&self, kind: StatusKind) -> bool {
+        self.0.iter().any(|effect| effect.kind == kind)
+    }
+}
 
-#[derive(Clone, Copy, Debug, PartialEq)]
-pub struct Weapon;
+/// Effects a monster's attacks put on whatever they hit.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct InflictsStatus(pub Vec<StatusEffect>);
@@ -0,0 +1,137 @@
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// A damage roll written the way `template.ron` writes it: `1d6+1`, `2d4`, or a flat `3`.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Dice(pub DiceType);
+
+impl Dice {
+    pub fn flat(amount: i32) -> Self {
+        Self(DiceType::new(0, 0, amount))
+    }
+
+    /// Critical hits roll twice as many dice, but don't double the bonus.
+    pub fn roll(&self, rng: &mut RandomNumberGenerator, critical: bool) -> i32 {
+        let n_dice = if critical {
+            self.0.n_dice * 2
+        } else {
+            self.0.n_dice
+        };
+        let rolled = if n_dice > 0 {
+            rng.roll_dice(n_dice, self.0.die_type)
+        } else {
+            0
+        };
+
+        rolled + self.0.bonus
+    }
+}
+
+impl fmt::Display for Dice {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let DiceType {
+            n_dice,
+            die_type,
+            bonus,
+        } = self.0;
+        match (n_dice, bonus) {
+            (0, _) => write!(f, "{}", bonus),
+            (_, 0) => write!(f, "{}d{}", n_dice, die_type),
+            _ => write!(f, "{}d{}{:+}", n_dice, die_type, bonus),
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct DiceError(pub String);
+
+impl fmt::Display for DiceError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "\"{}\" isn't a dice roll like \"1d6+1\", \"2d4\" or \"3\"",
+            self.0
+        )
+    }
+}
+
+impl std::error::Error for DiceError {}
+
+impl std::str::FromStr for Dice {
+    type Err = DiceError;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let error = || DiceError(text.to_string());
+        let number = |text: &str| text.parse::<i32>().map_err(|_| error());
+        let text = text.trim();
+
+        let (dice, bonus) = match text.find(['+', '-']).filter(|&i| i > 0) {
+            Some(i) => (&text[..i], number(&text[i..])?),
+            None => (text, 0),
+        };
+        let (n_dice, die_type) = match dice.split_once('d') {
+            Some(("", die_type)) => (1, number(die_type)?),
+            Some((n_dice, die_type)) => (number(n_dice)?, number(die_type)?),
+            None => return number(text).map(Dice::flat),
+        };
+        if n_dice < 1 || die_type < 1 {
+            return Err(error());
+        }
+
+        Ok(Self(DiceType::new(n_dice, die_type, bonus)))
+    }
+}
+
+impl TryFrom<String> for Dice {
+    type Error = DiceError;
+
+    fn try_from(text: String) -> Result<Self, Self::Error> {
+        text.parse()
+    }
+}
+
+impl From<Dice> for String {
+    fn from(dice: Dice) -> Self {
+        dice.to_string()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn dice(text: &str) -> Dice {
+        text.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(dice("1d6+1").0, DiceType::new(1, 6, 1));
+        assert_eq!(dice("d8").0, DiceType::new(1, 8, 0));
+        assert_eq!(dice(" 2d4-1 ").0, DiceType::new(2, 4, -1));
+        assert_eq!(dice("3"), Dice::flat(3));
+
+        for text in ["1d6+1", "2d4", "3d8-2", "3", "-1"] {
+            assert_eq!(dice(text).to_string(), text);
+        }
+        for text in ["", "d", "1d", "0d6", "1d6+", "1x6", "one"] {
+            assert!(text.parse::<Dice>().is_err(), "{:?} should not parse", text);
+        }
+    }
+
+    #[test]
+    fn test_rolls_stay_in_range() {
+        let mut rng = RandomNumberGenerator::seeded(3);
+        let sword = dice("1d6+1");
+
+        for _ in 0..100 {
+            assert!((2..=7).contains(&sword.roll(&mut rng, false)));
+            assert!((3..=13).contains(&sword.roll(&mut rng, true)));
+        }
+        assert_eq!(Dice::flat(2).roll(&mut rng, true), 2);
+    }
+}
@@ -0,0 +1,438 @@
+use std::fmt;
+
+use crate::prelude::*;
+
+/// What the number after an effect's name in `template.ron` stands for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum EffectParam {
+    /// The number is ignored.
+    Unused,
+    /// Hit points healed or dealt.
+    Hp,
+    /// How many turns a status lasts.
+    Turns,
+}
+
+impl EffectParam {
+    pub fn accepts(&self, amount: i32) -> bool {
+        match self {
+            EffectParam::Unused => true,
+            EffectParam::Hp | EffectParam::Turns => amount > 0,
+        }
+    }
+}
+
+/// Everything an effect can work with while an item is being used. Effects run inside
+/// `use_items`, so they can only touch the components that system declares.
+pub struct EffectContext<'a, 'w> {
+    pub ecs: &'a mut SubWorld<'w>,
+    pub commands: &'a mut CommandBuffer,
+    pub map: &'a mut Map,
+    pub index: &'a SpatialIndex,
+    pub rng: &'a mut RandomNumberGenerator,
+    pub log: &'a mut MessageLog,
+    pub user: Entity,
+    pub item: Entity,
+    /// Where the item was aimed, if it was fired from targeting mode.
+    pub target: Option<Point>,
+}
+
+impl EffectContext<'_, '_> {
+    fn user_name(&self) -> String {
+        display_name(self.ecs, self.user)
+    }
+
+    fn item_name(&self) -> String {
+        display_name(self.ecs, self.item)
+    }
+
+    fn user_pos(&self) -> Option<Point> {
+        let user = self.ecs.entry_ref(self.user).ok()?;
+        user.get_component::<Point>().ok().copied()
+    }
+
+    /// Whoever is standing where the item was aimed, or the user if it wasn't.
+    fn target_entity(&self) -> Option<Entity> {
+        match self.target {
+            Some(target) => self.index.creature_at(target),
+            None => Some(self.user),
+        }
+    }
+
+    /// "You read the Fireball Scroll, and `outcome`."
+    fn read(&mut self, category: MessageCategory, outcome: &str) {
+        let text = format!(
+            "{} {}, and {}.",
+            subject_verb(&self.user_name(), "read"),
+            self.item_name(),
+            outcome
+        );
+        self.log.add(category, text);
+    }
+}
+
+/// A named thing an item can do when it's used.
+pub struct Effect {
+    pub name: &'static str,
+    pub param: EffectParam,
+    /// A line for the inventory screen, given the effect's amount and the item it's on.
+    pub describe: fn(i32, &EntryRef) -> String,
+    pub apply: fn(&mut EffectContext, i32),
+}
+
+/// Every effect a template can list under `provides`.
+pub const EFFECTS: &[Effect] = &[
+    Effect {
+        name: "Healing",
+        param: EffectParam::Hp,
+        describe: |amount, _| format!("Heals up to {} hp when drunk.", amount),
+        apply: healing,
+    },
+    Effect {
+        name: "MagicMap",
+        param: EffectParam::Unused,
+        describe: |_, _| "Reveals the whole level when read.".to_string(),
+        apply: magic_map,
+    },
+    Effect {
+        name: "Fireball",
+        param: EffectParam::Hp,
+        describe: |amount, item| {
+            let radius = item.get_component::<AreaOfEffect>().map_or(0, |aoe| aoe.0);
+            format!(
+                "Burns everything within {} tiles of its target for {} damage.",
+                radius, amount
+            )
+        },
+        apply: fireball,
+    },
+    Effect {
+        name: "Lightning",
+        param: EffectParam::Hp,
+        describe: |amount, _| format!("Strikes the nearest enemy in sight for {} damage.", amount),
+        apply: lightning,
+    },
+    Effect {
+        name: "Teleport",
+        param: EffectParam::Unused,
+        describe: |_, _| "Sends you somewhere else on the level when read.".to_string(),
+        apply: teleport,
+    },
+    Effect {
+        name: "Poison",
+        param: EffectParam::Turns,
+        describe: |turns, item| describe_status(StatusKind::Poison, turns, item),
+        apply: |ctx, turns| status(ctx, StatusKind::Poison, turns),
+    },
+    Effect {
+        name: "Regeneration",
+        param: EffectParam::Turns,
+        describe: |turns, item| describe_status(StatusKind::Regeneration, turns, item),
+        apply: |ctx, turns| status(ctx, StatusKind::Regeneration, turns),
+    },
+    Effect {
+        name: "Confusion",
+        param: EffectParam::Turns,
+        describe: |turns, item| describe_status(StatusKind::Confusion, turns, item),
+        apply: |ctx, turns| status(ctx, StatusKind::Confusion, turns),
+    },
+    Effect {
+        name: "Slow",
+        param: EffectParam::Turns,
+        describe: |turns, item| describe_status(StatusKind::Slow, turns, item),
+        apply: |ctx, turns| status(ctx, StatusKind::Slow, turns),
+    },
+];
+
+pub fn find_effect(name: &str) -> Option<&'static Effect> {
+    EFFECTS.iter().find(|effect| effect.name == name)
+}
+
+/// An effect the registry doesn't have, or a number an effect can't use.
+#[derive(Clone, Debug, PartialEq)]
+pub enum EffectError {
+    Unknown {
+        name: String,
+    },
+    BadAmount {
+        name: String,
+        amount: i32,
+        param: EffectParam,
+    },
+}
+
+impl fmt::Display for EffectError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EffectError::Unknown { name } => {
+                let valid: Vec<&str> = EFFECTS.iter().map(|effect| effect.name).collect();
+                write!(
+                    f,
+                    "unknown effect \"{}\". Valid effects are: {}",
+                    name,
+                    valid.join(", ")
+                )
+            }
+            EffectError::BadAmount {
+                name,
+                amount,
+                param,
+            } => write!(
+                f,
+                "{} was given {}, but it needs a positive number of {:?}",
+                name, amount, param
+            ),
+        }
+    }
+}
+
+impl std::error::Error for EffectError {}
+
+/// Checks one `(name, amount)` pair from a template's `provides` list.
+pub fn check_effect(name: &str, amount: i32) -> Result<(), EffectError> {
+    let effect = find_effect(name).ok_or_else(|| EffectError::Unknown {
+        name: name.to_string(),
+    })?;
+    if !effect.param.accepts(amount) {
+        return Err(EffectError::BadAmount {
+            name: name.to_string(),
+            amount,
+            param: effect.param,
+        });
+    }
+    Ok(())
+}
+
+fn healing(ctx: &mut EffectContext, amount: i32) {
+    let user_name = ctx.user_name();
+    let item_name = ctx.item_name();
+    if let Ok(mut user) = ctx.ecs.entry_mut(ctx.user) {
+        if let Ok(health) = user.get_component_mut::<Health>() {
+            let before = health.current;
+            health.current = i32::min(health.max, health.current + amount);
+            ctx.log.add(
+                MessageCategory::Item,
+                format!(
+                    "{} {} (+{} hp).",
+                    subject_verb(&user_name, "drink"),
+                    item_name,
+                    health.current - before
+                ),
+            );
+        }
+    }
+}
+
+fn magic_map(ctx: &mut EffectContext, _: i32) {
+    for tile in ctx.map.revealed_tiles.iter_mut() {
+        *tile = true;
+    }
+    ctx.read(MessageCategory::Item, "the level is revealed");
+}
+
+fn fireball(ctx: &mut EffectContext, damage: i32) {
+    let centre = match ctx.target {
+        Some(centre) => centre,
+        None => return,
+    };
+    let radius = ctx
+        .ecs
+        .entry_ref(ctx.item)
+        .ok()
+        .and_then(|item| item.get_component::<AreaOfEffect>().ok().copied())
+        .map_or(0, |aoe| aoe.0);
+    ctx.read(MessageCategory::Combat, "flames engulf the area");
+
+    let victims: Vec<Entity> = <(Entity, &Point)>::query()
+        .filter(component::<Health>())
+        .iter(ctx.ecs)
+        .filter(|(_, pos)| DistanceAlg::Pythagoras.distance2d(**pos, centre) < radius as f32 + 0.5)
+        .map(|(entity, _)| *entity)
+        .collect();
+    for victim in victims {
+        hurt(ctx, victim, damage, "burned");
+    }
+}
+
+fn lightning(ctx: &mut EffectContext, damage: i32) {
+    let nearest = ctx.ecs.entry_ref(ctx.user).ok().and_then(|user| {
+        let pos = user.get_component::<Point>().ok()?;
+        let fov = user.get_component::<FieldOfView>().ok()?;
+        visible_targets(ctx.ecs, *pos, fov).first().copied()
+    });
+    match nearest {
+        Some((victim, _)) => {
+            ctx.read(MessageCategory::Combat, "lightning leaps out");
+            hurt(ctx, victim, damage, "struck");
+        }
+        None => ctx.read(MessageCategory::Info, "the lightning has nothing to strike"),
+    }
+}
+
+fn teleport(ctx: &mut EffectContext, _: i32) {
+    let destination = match ctx.user_pos() {
+        Some(pos) => teleport_destination(ctx, pos),
+        None => return,
+    };
+    if let Some(destination) = destination {
+        ctx.commands.push((
+            (),
+            WantsToMove {
+                entity: ctx.user,
+                destination,
+            },
+        ));
+        ctx.read(MessageCategory::Item, "the world lurches");
+    }
+}
+
+/// A random floor tile that could be walked to from `from` and that nobody is standing on.
+fn teleport_destination(ctx: &mut EffectContext, from: Point) -> Option<Point> {
+    let map = &*ctx.map;
+    let reachable = DijkstraMap::new(
+        map.width,
+        map.height,
+        &[map.point2d_to_index(from)],
+        map,
+        MAX_FLOWMAP_DISTANCE,
+    );
+    let candidates: Vec<Point> = reachable
+        .map
+        .iter()
+        .enumerate()
+        .filter(|(idx, distance)| {
+            **distance < MAX_FLOWMAP_DISTANCE && map.tiles[*idx] == TileType::Floor
+        })
+        .map(|(idx, _)| map.index_to_point2d(idx))
+        .filter(|pos| *pos != from && ctx.index.creature_at(*pos).is_none())
+        .collect();
+    if candidates.is_empty() {
+        return None;
+    }
+
+    Some(candidates[ctx.rng.range(0, candidates.len())])
+}
+
+/// Aimed items put their status on the target instead of the user.
+fn status(ctx: &mut EffectContext, kind: StatusKind, turns: i32) {
+    if let Some(target) = ctx.target_entity() {
+        let effect = StatusEffect::new(kind, turns);
+        apply_status(ctx.ecs, ctx.commands, ctx.log, target, &[effect]);
+    }
+}
+
+/// Puts `effects` on `target`, on top of any it already has, and says so in the log. The caller
+/// has to be able to read `StatusEffects`, `Player` and `Name`.
+pub fn apply_status(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    log: &mut MessageLog,
+    target: Entity,
+    effects: &[StatusEffect],
+) {
+    let mut active = ecs
+        .entry_ref(target)
+        .ok()
+        .and_then(|entry| entry.get_component::<StatusEffects>().ok().cloned())
+        .unwrap_or_default();
+
+    let name = display_name(ecs, target);
+    for effect in effects {
+        active.add(*effect);
+        let text = if name == "you" {
+            format!("You are {}.", effect.kind.name())
+        } else {
+            format!("{} is {}.", capitalize(&name), effect.kind.name())
+        };
+        let category = match (effect.kind, name == "you") {
+            (StatusKind::Regeneration, _) => MessageCategory::Item,
+            (_, true) => MessageCategory::Danger,
+            (_, false) => MessageCategory::Combat,
+        };
+        log.add(category, text);
+    }
+    commands.add_component(target, active);
+}
+
+fn describe_status(kind: StatusKind, turns: i32, item: &EntryRef) -> String {
+    let whom = if item.get_component::<Ranged>().is_ok() {
+        "its target"
+    } else {
+        "you"
+    };
+    format!("Leaves {} {} for {} turns.", whom, kind.name(), turns)
+}
+
+/// Takes `amount` hp from `victim`, killing it if it's a monster that runs out.
+fn hurt(ctx: &mut EffectContext, victim: Entity, amount: i32, verb: &str) {
+    let victim_name = display_name(ctx.ecs, victim);
+    let mut entry = match ctx.ecs.entry_mut(victim) {
+        Ok(entry) => entry,
+        Err(_) => return,
+    };
+    let is_player = entry.get_component::<Player>().is_ok();
+    if let Ok(health) = entry.get_component_mut::<Health>() {
+        health.current -= amount;
+        let (text, category) = if is_player {
+            (
+                format!("You are {} for {}.", verb, amount),
+                MessageCategory::Danger,
+            )
+        } else {
+            (
+                format!("{} is {} for {}.", capitalize(&victim_name), verb, amount),
+                MessageCategory::Combat,
+            )
+        };
+        ctx.log.add(category, text);
+
+        if health.current < 1 && !is_player {
+            ctx.commands.remove(victim);
+            ctx.log
+                .add(category, format!("{} dies.", capitalize(&victim_name)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_effect_names_are_unique() {
+        for (i, effect) in EFFECTS.iter().enumerate() {
+            assert!(
+                EFFECTS[i + 1..]
+                    .iter()
+                    .all(|other| other.name != effect.name),
+                "{} is registered twice",
+                effect.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_unknown_effects_list_the_valid_ones() {
+        let err = check_effect("Levitation", 3).unwrap_err();
+        let message = err.to_string();
+
+        assert!(message.contains("Levitation"));
+        for effect in EFFECTS {
+            assert!(
+                message.contains(effect.name),
+                "{} isn't listed",
+                effect.name
+            );
+        }
+    }
+
+    #[test]
+    fn test_amounts_are_checked_against_the_schema() {
+        assert!(check_effect("Healing", 6).is_ok());
+        assert!(check_effect("MagicMap", 0).is_ok());
+        assert!(matches!(
+            check_effect("Healing", 0),
+            Err(EffectError::BadAmount { amount: 0, .. })
+        ));
+    }
+}
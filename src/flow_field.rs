@@ -0,0 +1,224 @@
+use crate::prelude::*;
+
+/// How much more a fleeing monster cares about ending up far away than about the walk there.
+/// Anything above 1 makes it worth doubling back past the player's side of a dead end.
+const FLEE_SCALE: f32 = 1.2;
+
+/// Walking distances to the player, shared by every AI system. The `flow_field` system keeps it
+/// up to date at the start of the monsters' turn, and it's only rebuilt when the player has moved
+/// or the map has changed since last time.
+#[derive(Default)]
+pub struct FlowField {
+    player_pos: Option<Point>,
+    /// The tiles the field was built for, so opened doors and new levels are noticed.
+    tiles: Vec<TileType>,
+    distances: Option<DijkstraMap>,
+    /// Built on demand from `distances`, since most turns nobody is running away.
+    safety: Option<DijkstraMap>,
+}
+
+impl FlowField {
+    /// Rebuilds the field if it's out of date, returning whether it had to.
+    pub fn update(&mut self, map: &Map, player_pos: Point) -> bool {
+        if self.player_pos == Some(player_pos) && self.tiles == map.tiles {
+            return false;
+        }
+
+        self.distances = Some(DijkstraMap::new(
+            map.width,
+            map.height,
+            &[map.point2d_to_index(player_pos)],
+            map,
+            MAX_FLOWMAP_DISTANCE,
+        ));
+        self.safety = None;
+        self.player_pos = Some(player_pos);
+        self.tiles = map.tiles.clone();
+        true
+    }
+
+    /// How far every tile is from the player, or `None` before the first `update`.
+    pub fn distances(&self) -> Option<&DijkstraMap> {
+        self.distances.as_ref()
+    }
+
+    /// A map that's lowest where it's safest to be from the player. See `safety_map`.
+    pub fn safety(&mut self, map: &Map) -> Option<&DijkstraMap> {
+        if self.safety.is_none() {
+            self.safety = Some(safety_map(map, self.distances.as_ref()?));
+        }
+        self.safety.as_ref()
+    }
+}
+
+/// Turns a map of distances to the player into one that's lowest where it's safest to be. The
+/// distances are flipped and scaled, then relaxed again, so walking downhill leads out of dead
+/// ends towards wherever is furthest away instead of into the nearest corner.
+pub fn safety_map(map: &Map, distances: &DijkstraMap) -> DijkstraMap {
+    let mut safety = DijkstraMap::new_empty(map.width, map.height, MAX_FLOWMAP_DISTANCE);
+    let starts: Vec<(usize, f32)> = distances
+        .map
+        .iter()
+        .enumerate()
+        .filter(|(_, distance)| **distance < MAX_FLOWMAP_DISTANCE)
+        .map(|(idx, distance)| (idx, -distance * FLEE_SCALE))
+        .collect();
+    for (idx, value) in starts.iter() {
+        safety.map[*idx] = *value;
+    }
+    DijkstraMap::build_weighted(&mut safety, &starts, map);
+
+    safety
+}
+
+#[cfg(test)]
+mod test {
+    use std::time::{Duration, Instant};
+
+    use super::*;
+
+    #[test]
+    fn test_only_rebuilt_when_the_player_moves_or_the_map_changes() {
+        let mut map = Map::new(10, 10);
+        let mut field = FlowField::default();
+
+        assert!(field.update(&map, Point::new(1, 1)));
+        assert!(!field.update(&map, Point::new(1, 1)));
+        assert_eq!(
+            field.distances().unwrap().map[map.idx(3, 1)],
+            2.0,
+            "distances should be walked from the player"
+        );
+
+        assert!(field.update(&map, Point::new(2, 1)));
+        assert!(!field.update(&map, Point::new(2, 1)));
+
+        let idx = map.idx(5, 5);
+        map.tiles[idx] = TileType::Wall;
+        assert!(field.update(&map, Point::new(2, 1)));
+    }
+
+    #[test]
+    fn test_safety_map_is_cached_until_the_next_rebuild() {
+        let map = Map::new(10, 10);
+        let mut field = FlowField::default();
+        assert!(field.safety(&map).is_none());
+
+        field.update(&map, Point::new(1, 1));
+        let corner = map.idx(9, 9);
+        let safest = field.safety(&map).unwrap().map[corner];
+        assert!(field.safety.is_some());
+        assert!(safest < field.safety(&map).unwrap().map[map.idx(2, 1)]);
+
+        field.update(&map, Point::new(8, 8));
+        assert!(field.safety.is_none());
+    }
+
+    /// A real level with 50 extra chasers on it, spread over its floor, and a player who won't
+    /// die of them.
+    fn crowded_game() -> Game {
+        let mut game = Game::new().unwrap();
+        game.start(RunSeed(1));
+        let open_floor: Vec<Point> = {
+            let map = game.resources.get::<Map>().unwrap();
+            (0..map.tiles.len())
+                .filter(|idx| map.tiles[*idx] == TileType::Floor)
+                .map(|idx| map.index_to_point2d(idx))
+                .collect()
+        };
+        let spacing = (open_floor.len() / 50).max(1);
+        for pos in open_floor.iter().step_by(spacing).take(50) {
+            game.ecs.push((
+                Enemy,
+                ChasingPlayer,
+                Name("Goblin".to_string()),
+                Health::new(1000),
+                FieldOfView::new(6),
+                *pos,
+            ));
+        }
+        for health in <&mut Health>::query()
+            .filter(component::<Player>())
+            .iter_mut(&mut game.ecs)
+        {
+            *health = Health::new(1_000_000);
+        }
+        game
+    }
+
+    /// Times the monsters' half of each turn with 50 extra chasers about, both with the shared
+    /// field and with it thrown away before every monster turn, so it's rebuilt every time the way
+    /// each AI system used to build its own. Then times one of those builds against finding the
+    /// field already up to date. Run it with
+    /// `cargo test --release bench_ -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_monster_turns_with_50_monsters() {
+        let turn_time = |keys: &[VirtualKeyCode], shared: bool| {
+            let mut game = crowded_game();
+            let mut monster_time = Duration::ZERO;
+            let turns = 200;
+            for key in keys.iter().cycle().take(turns) {
+                game.tick(Some(*key), Point::zero());
+                while game.turn_state() == TurnState::PlayerTurn {
+                    game.tick(None, Point::zero());
+                }
+                if !shared {
+                    game.resources.insert(FlowField::default());
+                }
+                let started = Instant::now();
+                while game.turn_state() == TurnState::MonsterTurn {
+                    game.tick(None, Point::zero());
+                }
+                monster_time += started.elapsed();
+            }
+            monster_time.as_secs_f64() * 1000.0 / turns as f64
+        };
+
+        let waiting = [VirtualKeyCode::Space];
+        let pacing = [VirtualKeyCode::Left, VirtualKeyCode::Right];
+        for (label, keys) in [
+            ("player waiting", &waiting[..]),
+            ("player pacing", &pacing[..]),
+        ] {
+            println!(
+                "{}: {:.3}ms per monster turn rebuilding every turn, {:.3}ms shared",
+                label,
+                turn_time(keys, false),
+                turn_time(keys, true)
+            );
+        }
+
+        let game = crowded_game();
+        let map = game.resources.get::<Map>().unwrap();
+        let player_pos = *<&Point>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .next()
+            .unwrap();
+        let runs = 1000;
+        let started = Instant::now();
+        for _ in 0..runs {
+            DijkstraMap::new(
+                map.width,
+                map.height,
+                &[map.point2d_to_index(player_pos)],
+                &*map,
+                MAX_FLOWMAP_DISTANCE,
+            );
+        }
+        let built = started.elapsed();
+        let mut field = FlowField::default();
+        field.update(&map, player_pos);
+        let started = Instant::now();
+        for _ in 0..runs {
+            assert!(!field.update(&map, player_pos));
+        }
+        let cached = started.elapsed();
+        println!(
+            "DijkstraMap::new: {:.1}us, FlowField::update when up to date: {:.1}us",
+            built.as_secs_f64() * 1e6 / runs as f64,
+            cached.as_secs_f64() * 1e6 / runs as f64
+        );
+    }
+}
@@ -0,0 +1,406 @@
+use std::fmt;
+
+use crate::prelude::*;
+
+/// Why `Game::new` couldn't get the game's data files ready.
+#[derive(Debug)]
+pub enum GameError {
+    Templates(TemplateError),
+    Prefabs(PrefabError),
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::Templates(err) => write!(f, "{}", err),
+            GameError::Prefabs(err) => write!(f, "{}", err),
+        }
+    }
+}
+
+impl std::error::Error for GameError {}
+
+/// Everything needed to play a run, without any dependency on a `BTerm` window.
+pub struct Game {
+    pub ecs: World,
+    pub resources: Resources,
+    prefabs: Prefabs,
+    templates: Templates,
+    /// Prefabs that didn't fit on the levels built so far, until `take_placement_errors`.
+    placement_errors: Vec<PlacementError>,
+    input_systems: Schedule,
+    player_systems: Schedule,
+    monster_systems: Schedule,
+}
+
+impl Game {
+    pub fn new() -> Result<Self, GameError> {
+        let ecs = World::default();
+        let mut resources = Resources::default();
+        // These get recreated immediately on start, so they should probably just be optional.
+        resources.insert(TurnState::MainMenu);
+
+        let templates = Templates::load().map_err(GameError::Templates)?;
+        let prefabs = Prefabs::load()
+            .and_then(|prefabs| prefabs.check_templates(&templates).map(|_| prefabs))
+            .map_err(GameError::Prefabs)?;
+
+        Ok(Self {
+            ecs,
+            resources,
+            prefabs,
+            templates,
+            placement_errors: Vec::new(),
+            input_systems: build_input_scheduler(),
+            player_systems: build_player_scheduler(),
+            monster_systems: build_monster_scheduler(),
+        })
+    }
+
+    pub fn start(&mut self, seed: RunSeed) {
+        let mut rng = seed.level_rng(0);
+        let mut map_builder =
+            MapBuilder::new(MAP_WIDTH, MAP_HEIGHT).build(0, &self.prefabs, &mut rng);
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+
+        self.placement_errors
+            .append(&mut map_builder.placement_errors);
+        let exit_idx = map_builder.map.point2d_to_index(map_builder.amulet_start);
+        map_builder.map.tiles[exit_idx] = TileType::Exit;
+        spawn_player(&mut ecs, map_builder.player_start);
+        // spawn_amulet_of_yala(&mut ecs, map_builder.amulet_start);
+
+        Game::spawn_level(
+            &mut ecs,
+            &mut resources,
+            &self.templates,
+            &mut rng,
+            0,
+            &map_builder,
+        );
+
+        resources.insert(seed);
+        resources.insert(rng);
+        resources.insert(map_builder.map);
+        resources.insert(Camera::new(map_builder.player_start));
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(map_builder.theme);
+        resources.insert(LevelStore::default());
+        let mut log = MessageLog::default();
+        log.add(
+            MessageCategory::Event,
+            "You enter the dungeon in search of the Amulet of Yala.",
+        );
+        resources.insert(log);
+        resources.insert(InventoryScreen::default());
+        resources.insert(Targeting::default());
+        resources.insert(FlowField::default());
+
+        self.ecs = ecs;
+        self.resources = resources;
+        self.index_entities();
+    }
+
+    pub fn load(&mut self, ecs: World, resources: Resources) {
+        self.ecs = ecs;
+        self.resources = resources;
+        self.index_entities();
+    }
+
+    /// Hands over the prefabs that couldn't be placed since the last call, for the caller to
+    /// report however it likes.
+    pub fn take_placement_errors(&mut self) -> Vec<PlacementError> {
+        std::mem::take(&mut self.placement_errors)
+    }
+
+    pub fn turn_state(&self) -> TurnState {
+        *self.resources.get::<TurnState>().unwrap()
+    }
+
+    pub fn map_level(&self) -> usize {
+        <&Player>::query()
+            .iter(&self.ecs)
+            .map(|player| player.map_level)
+            .next()
+            .unwrap()
+    }
+
+    pub fn is_in_progress(&self) -> bool {
+        !matches!(
+            self.turn_state(),
+            TurnState::MainMenu | TurnState::GameOver | TurnState::Victory
+        )
+    }
+
+    /// Runs whichever schedule the current `TurnState` calls for, feeding it this frame's input.
+    pub fn tick(&mut self, key: Option<VirtualKeyCode>, mouse_pos: Point) {
+        self.resources.insert(key);
+        self.resources.insert(mouse_pos);
+
+        match self.turn_state() {
+            TurnState::AwaitingInput => self
+                .input_systems
+                .execute(&mut self.ecs, &mut self.resources),
+            TurnState::PlayerTurn => self
+                .player_systems
+                .execute(&mut self.ecs, &mut self.resources),
+            TurnState::MonsterTurn => self
+                .monster_systems
+                .execute(&mut self.ecs, &mut self.resources),
+            TurnState::NextLevel => self.change_level(self.map_level() + 1),
+            TurnState::PreviousLevel => self.change_level(self.map_level().saturating_sub(1)),
+            TurnState::MainMenu | TurnState::GameOver | TurnState::Victory => {}
+        }
+    }
+
+    pub fn spawn_level(
+        ecs: &mut World,
+        resources: &mut Resources,
+        templates: &Templates,
+        rng: &mut RandomNumberGenerator,
+        level: usize,
+        map_builder: &MapBuilder,
+    ) {
+        templates.spawn_entities(ecs, resources, rng, level, &map_builder.monster_spawns);
+        templates.spawn_items(ecs, resources, rng, level, &map_builder.item_spawns);
+        templates.spawn_named(ecs, resources, &map_builder.template_spawns);
+    }
+
+    /// Moves the player to `map_level`, parking the level they're leaving in the `LevelStore` and
+    /// restoring the one they're entering from it, or building it if it's never been visited.
+    fn change_level(&mut self, map_level: usize) {
+        let current_level = self.map_level();
+
+        park_level(&mut self.ecs, &mut self.resources, current_level);
+
+        let mut levels = self.resources.remove::<LevelStore>().unwrap_or_default();
+        let map = self.resources.remove::<Map>().unwrap();
+        let theme = self.resources.remove::<Box<dyn MapTheme>>().unwrap().id();
+        levels.store(current_level, map, theme);
+
+        let (map, theme, player_start) = match levels.take(map_level) {
+            Some(StoredLevel { map, theme }) => {
+                unpark_level(&mut self.ecs, &mut self.resources, map_level);
+
+                let stairs = if map_level > current_level {
+                    TileType::UpStairs
+                } else {
+                    TileType::Exit
+                };
+                let player_start = map
+                    .find_tile(stairs)
+                    .and_then(|stairs| map.nearest_floor(stairs))
+                    .unwrap_or_else(|| map.center());
+
+                (map, theme.theme(), player_start)
+            }
+            None => self.build_level(map_level),
+        };
+
+        for (player, pos, fov) in
+            <(&mut Player, &mut Point, &mut FieldOfView)>::query().iter_mut(&mut self.ecs)
+        {
+            player.map_level = map_level;
+            *pos = player_start;
+            fov.is_dirty = true;
+        }
+
+        if let Some(mut log) = self.resources.get_mut::<MessageLog>() {
+            let direction = if map_level > current_level {
+                "descend"
+            } else {
+                "climb back up"
+            };
+            log.add(
+                MessageCategory::Event,
+                format!("You {} to dungeon level {}.", direction, map_level),
+            );
+        }
+
+        self.resources.insert(levels);
+        self.resources.insert(map);
+        self.resources.insert(Camera::new(player_start));
+        self.resources.insert(TurnState::AwaitingInput);
+        self.resources.insert(theme);
+        self.index_entities();
+    }
+
+    /// Brings the `SpatialIndex` up to date straight away, so nothing drawn before the next turn
+    /// schedule runs sees where things used to be.
+    fn index_entities(&mut self) {
+        let mut index = SpatialIndex::default();
+        index.rebuild(&self.resources.get::<Map>().unwrap(), &self.ecs);
+        self.resources.insert(index);
+    }
+
+    /// Builds and populates a level the player hasn't been to yet, returning its map, its theme
+    /// and where the player arrives.
+    fn build_level(&mut self, map_level: usize) -> (Map, Box<dyn MapTheme>, Point) {
+        let mut rng = self
+            .resources
+            .get::<RunSeed>()
+            .unwrap()
+            .level_rng(map_level);
+        let mut map_builder =
+            MapBuilder::new(MAP_WIDTH, MAP_HEIGHT).build(map_level, &self.prefabs, &mut rng);
+        self.placement_errors
+            .append(&mut map_builder.placement_errors);
+
+        if map_level == 2 {
+            spawn_amulet_of_yala(&mut self.ecs, map_builder.amulet_start);
+        } else {
+            let exit_idx = map_builder.map.point2d_to_index(map_builder.amulet_start);
+            map_builder.map.tiles[exit_idx] = TileType::Exit;
+        }
+
+        let mut player_start = map_builder.player_start;
+        if map_level > 0 {
+            let stairs_idx = map_builder.map.point2d_to_index(player_start);
+            map_builder.map.tiles[stairs_idx] = TileType::UpStairs;
+            if let Some(floor) = map_builder.map.nearest_floor(player_start) {
+                player_start = floor;
+            }
+            map_builder
+                .monster_spawns
+                .retain(|&spawn| spawn != player_start);
+        }
+
+        Game::spawn_level(
+            &mut self.ecs,
+            &mut self.resources,
+            &self.templates,
+            &mut rng,
+            map_level,
+            &map_builder,
+        );
+
+        self.resources.insert(rng);
+        (map_builder.map, map_builder.theme, player_start)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Starts a run, then swaps its first level for one built at `width` by `height`. `place` can
+    /// move the player's start before anything is spawned.
+    fn start_at_size(
+        width: i32,
+        height: i32,
+        place: impl Fn(&MapBuilder) -> Option<Point>,
+    ) -> Game {
+        let mut game = Game::new().unwrap();
+        game.start(RunSeed(1));
+        let mut rng = RunSeed(1).level_rng(0);
+        let mut map_builder = MapBuilder::new(width, height).build(0, &game.prefabs, &mut rng);
+        if let Some(start) = place(&map_builder) {
+            map_builder.player_start = start;
+            map_builder.monster_spawns.retain(|&spawn| spawn != start);
+        }
+
+        let old_level: Vec<Entity> = <Entity>::query()
+            .filter(component::<Point>() & !component::<Player>())
+            .iter(&game.ecs)
+            .copied()
+            .collect();
+        for entity in old_level {
+            game.ecs.remove(entity);
+        }
+        for (pos, fov) in <(&mut Point, &mut FieldOfView)>::query()
+            .filter(component::<Player>())
+            .iter_mut(&mut game.ecs)
+        {
+            *pos = map_builder.player_start;
+            fov.is_dirty = true;
+        }
+        Game::spawn_level(
+            &mut game.ecs,
+            &mut game.resources,
+            &game.templates,
+            &mut rng,
+            0,
+            &map_builder,
+        );
+        game.resources.insert(Camera::new(map_builder.player_start));
+        game.resources.insert(map_builder.map);
+        game.index_entities();
+
+        game
+    }
+
+    /// Plays a few turns, then checks that sight, the camera and the monsters' flow field all
+    /// went by the map's own size.
+    fn play_and_check(mut game: Game, width: i32, height: i32) {
+        let keys =
+            parse_key_codes("Right,Right,Down,Down,Left,Up,Space,Right,Down,Left,Left,Up,Space")
+                .unwrap();
+        step_headless(&mut game, &keys);
+
+        let map = game.resources.get::<Map>().unwrap();
+        assert_eq!(map.dimensions(), Point::new(width, height));
+        assert_eq!(map.tiles.len(), (width * height) as usize);
+
+        let (player_pos, fov) = <(&Point, &FieldOfView)>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .map(|(pos, fov)| (*pos, fov.clone()))
+            .next()
+            .unwrap();
+        assert!(map.in_bounds(player_pos));
+        assert!(fov.visible_tiles.contains(&player_pos));
+        assert!(fov.visible_tiles.iter().all(|&pt| map.in_bounds(pt)));
+        assert_eq!(
+            *game.resources.get::<Camera>().unwrap(),
+            Camera::new(player_pos)
+        );
+
+        assert_eq!(game.turn_state(), TurnState::AwaitingInput);
+        let flow = game.resources.get::<FlowField>().unwrap();
+        let distances = flow.distances().unwrap();
+        assert_eq!(distances.map.len(), (width * height) as usize);
+        assert!(Map::directions().into_iter().any(|direction| {
+            map.try_idx(player_pos + direction)
+                .is_some_and(|idx| distances.map[idx] == 1.0)
+        }));
+    }
+
+    #[test]
+    fn test_plays_a_small_arena() {
+        let game = start_at_size(30, 20, |_| None);
+
+        play_and_check(game, 30, 20);
+    }
+
+    #[test]
+    fn test_plays_a_big_cave() {
+        // Starting past where an 80x50 map would end catches anything still using that size.
+        let game = start_at_size(200, 120, |map_builder| {
+            let map = &map_builder.map;
+            let distances = DijkstraMap::new(
+                map.width,
+                map.height,
+                &[map.point2d_to_index(map_builder.player_start)],
+                map,
+                MAX_FLOWMAP_DISTANCE,
+            );
+            (0..map.tiles.len())
+                .filter(|&idx| {
+                    map.tiles[idx] == TileType::Floor && distances.map[idx] < MAX_FLOWMAP_DISTANCE
+                })
+                .map(|idx| map.index_to_point2d(idx))
+                .filter(|pt| pt.x >= MAP_WIDTH && pt.y >= MAP_HEIGHT)
+                .max_by_key(|pt| pt.x)
+        });
+        let player_pos = <&Point>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .copied()
+            .next()
+            .unwrap();
+        assert!(player_pos.x >= MAP_WIDTH && player_pos.y >= MAP_HEIGHT);
+
+        play_and_check(game, 200, 120);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::prelude::*;
+
+/// How a headless run ended up once its scripted keys ran out.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HeadlessReport {
+    pub seed: RunSeed,
+    pub turn_state: TurnState,
+    pub keys_used: usize,
+    pub map_level: usize,
+}
+
+/// Plays a run without a window, feeding `keys` in one at a time whenever the game is waiting
+/// for input. Stops when the keys run out or the run ends.
+pub fn run_headless(seed: RunSeed, keys: &[VirtualKeyCode]) -> Result<HeadlessReport, GameError> {
+    let mut game = Game::new()?;
+    game.start(seed);
+    let keys_used = step_headless(&mut game, keys);
+
+    Ok(HeadlessReport::new(&game, seed, keys_used))
+}
+
+impl HeadlessReport {
+    pub fn new(game: &Game, seed: RunSeed, keys_used: usize) -> Self {
+        Self {
+            seed,
+            turn_state: game.turn_state(),
+            keys_used,
+            map_level: game.map_level(),
+        }
+    }
+}
+
+/// Steps an already started `Game` through `keys`, returning how many of them were used.
+pub fn step_headless(game: &mut Game, keys: &[VirtualKeyCode]) -> usize {
+    let mut keys_used = 0;
+
+    while game.is_in_progress() {
+        if game.turn_state() == TurnState::AwaitingInput {
+            match keys.get(keys_used) {
+                Some(&key) => {
+                    keys_used += 1;
+                    game.tick(Some(key), Point::zero());
+                }
+                None => break,
+            }
+        } else {
+            game.tick(None, Point::zero());
+        }
+    }
+
+    keys_used
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn player_state(game: &Game) -> (Point, Health) {
+        <(&Point, &Health)>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .map(|(pos, health)| (*pos, *health))
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_no_keys() {
+        let report = run_headless(RunSeed(1), &[]).unwrap();
+
+        assert_eq!(report.turn_state, TurnState::AwaitingInput);
+        assert_eq!(report.keys_used, 0);
+        assert_eq!(report.map_level, 0);
+    }
+
+    #[test]
+    fn test_keys_complete_turns() {
+        let keys = [VirtualKeyCode::Space; 5];
+        let report = run_headless(RunSeed(1), &keys).unwrap();
+
+        assert!(report.keys_used == keys.len() || report.turn_state == TurnState::GameOver);
+        assert_ne!(report.turn_state, TurnState::PlayerTurn);
+        assert_ne!(report.turn_state, TurnState::MonsterTurn);
+    }
+
+    #[test]
+    fn test_same_script_same_outcome() {
+        let keys = parse_key_codes("Up,Up,Left,Left,Down,Right,G,Down,Down,Right,Up,Left").unwrap();
+
+        let mut first = Game::new().unwrap();
+        first.start(RunSeed(42));
+        step_headless(&mut first, &keys);
+
+        let mut second = Game::new().unwrap();
+        second.start(RunSeed(42));
+        step_headless(&mut second, &keys);
+
+        assert_eq!(first.turn_state(), second.turn_state());
+        assert_eq!(player_state(&first), player_state(&second));
+        assert_eq!(first.ecs.len(), second.ecs.len());
+    }
+}
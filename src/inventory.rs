@@ -0,0 +1,343 @@
+use crate::prelude::*;
+
+/// Each page of the inventory screen lists as many items as there are letters.
+pub const ITEMS_PER_PAGE: usize = 26;
+
+/// What picking an item from the inventory screen does to it.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum InventoryMode {
+    Use,
+    Drop,
+    Examine,
+}
+
+impl InventoryMode {
+    pub fn next(self) -> Self {
+        match self {
+            InventoryMode::Use => InventoryMode::Drop,
+            InventoryMode::Drop => InventoryMode::Examine,
+            InventoryMode::Examine => InventoryMode::Use,
+        }
+    }
+
+    pub fn title(self) -> &'static str {
+        match self {
+            InventoryMode::Use => "Use which item?",
+            InventoryMode::Drop => "Drop which item?",
+            InventoryMode::Examine => "Examine which item?",
+        }
+    }
+}
+
+/// The modal inventory screen. While it's open, `player_input` sends every key to it instead of
+/// moving the player.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct InventoryScreen {
+    pub mode: Option<InventoryMode>,
+    pub page: usize,
+    pub examining: Option<Entity>,
+}
+
+impl InventoryScreen {
+    pub fn open(&mut self, mode: InventoryMode) {
+        *self = Self {
+            mode: Some(mode),
+            ..Self::default()
+        };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.mode.is_some()
+    }
+
+    pub fn turn_page(&mut self, pages: i32, item_count: usize) {
+        let last_page = item_count.saturating_sub(1) / ITEMS_PER_PAGE;
+        self.page = (self.page as i32 + pages).clamp(0, last_page as i32) as usize;
+    }
+
+    /// The items shown on the current page, alongside their letters.
+    pub fn page_items<'a>(
+        &self,
+        items: &'a [(Entity, String)],
+    ) -> impl Iterator<Item = (char, &'a (Entity, String))> {
+        items
+            .iter()
+            .skip(self.page * ITEMS_PER_PAGE)
+            .take(ITEMS_PER_PAGE)
+            .zip('a'..='z')
+            .map(|(item, letter)| (letter, item))
+    }
+
+    pub fn item_for_key(&self, items: &[(Entity, String)], key: VirtualKeyCode) -> Option<Entity> {
+        let letter = letter_for_key(key)?;
+        self.page_items(items)
+            .find(|(item_letter, _)| *item_letter == letter)
+            .map(|(_, (entity, _))| *entity)
+    }
+}
+
+fn letter_for_key(key: VirtualKeyCode) -> Option<char> {
+    let offset = (key as u32).checked_sub(VirtualKeyCode::A as u32)?;
+    if offset < 26 {
+        char::from_u32('a' as u32 + offset)
+    } else {
+        None
+    }
+}
+
+/// Everything `owner` is carrying, ordered by name and then by entity so each item keeps its place
+/// in the list however legion happens to store them. Equipped items say which slot they're in.
+pub fn carried_items<S: EntityStore>(ecs: &S, owner: Entity) -> Vec<(Entity, String)> {
+    let mut items: Vec<(Entity, String)> = <(Entity, &Name, &Carried, Option<&Equipped>)>::query()
+        .filter(component::<Item>())
+        .iter(ecs)
+        .filter(|(_, _, carried, _)| carried.0 == owner)
+        .map(|(entity, name, _, equipped)| match equipped {
+            Some(Equipped(slot)) => (*entity, format!("{} ({})", name.0, slot.name())),
+            None => (*entity, name.0.clone()),
+        })
+        .collect();
+    // Legion's `Entity` isn't `Ord`, but its debug form is stable and tells items apart.
+    items.sort_by_cached_key(|(entity, name)| (name.clone(), format!("{:?}", entity)));
+    items
+}
+
+/// How much an entity's own armor and the armor it has equipped take off each hit.
+pub fn defence<S: EntityStore>(ecs: &S, entity: Entity) -> i32 {
+    stat_with_equipment(ecs, entity, |armor: &Armor| armor.0)
+}
+
+pub fn accuracy<S: EntityStore>(ecs: &S, entity: Entity) -> i32 {
+    stat_with_equipment(ecs, entity, |accuracy: &Accuracy| accuracy.0)
+}
+
+pub fn evasion<S: EntityStore>(ecs: &S, entity: Entity) -> i32 {
+    stat_with_equipment(ecs, entity, |evasion: &Evasion| evasion.0)
+}
+
+/// The damage dice an entity rolls when it attacks in melee: its own, then those of its equipped
+/// gear. Ranged weapons only count when they're fired.
+pub fn damage_dice<S: EntityStore>(ecs: &S, entity: Entity) -> Vec<Dice> {
+    let own = ecs
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.get_component::<Damage>().ok().map(|damage| damage.0));
+    let mut dice: Vec<Dice> = own.into_iter().collect();
+    dice.extend(
+        <(&Carried, &Damage)>::query()
+            .filter(component::<Equipped>() & !component::<Ranged>())
+            .iter(ecs)
+            .filter(|(carried, _)| carried.0 == entity)
+            .map(|(_, damage)| damage.0),
+    );
+    dice
+}
+
+/// Adds up a stat from the entity's own component and from every item it has equipped.
+fn stat_with_equipment<S: EntityStore, T: legion::storage::Component>(
+    ecs: &S,
+    entity: Entity,
+    value: impl Fn(&T) -> i32,
+) -> i32 {
+    let natural = ecs
+        .entry_ref(entity)
+        .ok()
+        .and_then(|entry| entry.get_component::<T>().ok().map(&value))
+        .unwrap_or(0);
+    let worn: i32 = <(&Carried, &T)>::query()
+        .filter(component::<Equipped>())
+        .iter(ecs)
+        .filter(|(carried, _)| carried.0 == entity)
+        .map(|(_, stat)| value(stat))
+        .sum();
+
+    natural + worn
+}
+
+/// One line per thing an item does, for the examine view.
+pub fn describe_item<S: EntityStore>(ecs: &S, item: Entity) -> Vec<String> {
+    let mut lines = Vec::new();
+    let entry = match ecs.entry_ref(item) {
+        Ok(entry) => entry,
+        Err(_) => return lines,
+    };
+
+    if let Ok(provides) = entry.get_component::<Provides>() {
+        for provided in provides.0.iter() {
+            if let Some(effect) = find_effect(&provided.effect) {
+                lines.push((effect.describe)(provided.amount, &entry));
+            }
+        }
+    }
+    let equippable = entry.get_component::<Equippable>().ok();
+    let ranged = entry.get_component::<Ranged>().ok();
+    let is_weapon = entry.get_component::<Damage>().is_ok();
+    if let Ok(damage) = entry.get_component::<Damage>() {
+        if equippable.is_some() && ranged.is_none() {
+            lines.push(format!(
+                "Adds {} damage to your attacks while equipped.",
+                damage.0
+            ));
+        } else {
+            lines.push(format!("Deals {} damage.", damage.0));
+        }
+    }
+    match ranged {
+        Some(ranged) if is_weapon => {
+            lines.push(format!("Hits targets up to {} tiles away.", ranged.range))
+        }
+        Some(ranged) => lines.push(format!(
+            "Is aimed at a target up to {} tiles away.",
+            ranged.range
+        )),
+        None => {}
+    }
+    if let Ok(ammo) = entry.get_component::<Ammo>() {
+        lines.push(format!("{} shots left.", ammo.0));
+    }
+    if let Ok(armor) = entry.get_component::<Armor>() {
+        lines.push(format!("Adds {} defence while equipped.", armor.0));
+    }
+    if let Ok(accuracy) = entry.get_component::<Accuracy>() {
+        lines.push(format!("Adds {:+} accuracy while equipped.", accuracy.0));
+    }
+    if let Ok(evasion) = entry.get_component::<Evasion>() {
+        lines.push(format!("Adds {:+} evasion while equipped.", evasion.0));
+    }
+    if let Some(Equippable(slot)) = equippable {
+        let worn = if entry.get_component::<Equipped>().is_ok() {
+            "You have it equipped"
+        } else {
+            "It can be equipped"
+        };
+        lines.push(format!("{} in your {} slot.", worn, slot.name()));
+    }
+    if lines.is_empty() {
+        lines.push("It doesn't seem to do anything.".to_string());
+    }
+
+    lines
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn carry(world: &mut World, owner: Entity, name: &str) -> Entity {
+        world.push((Item, Name(name.to_string()), Carried(owner)))
+    }
+
+    #[test]
+    fn test_items_are_ordered_by_name() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        let sword = carry(&mut world, player, "Rusty Sword");
+        let potion = carry(&mut world, player, "Healing Potion");
+        let map = carry(&mut world, player, "Magic Map");
+        world.push((Item, Name("Huge Sword".to_string()), Point::zero()));
+        let ring = carry(&mut world, player, "Ring of Strength");
+        world
+            .entry(ring)
+            .unwrap()
+            .add_component(Equipped(EquipmentSlot::Ring));
+
+        let items = carried_items(&world, player);
+
+        assert_eq!(
+            items,
+            vec![
+                (potion, "Healing Potion".to_string()),
+                (map, "Magic Map".to_string()),
+                (ring, "Ring of Strength (ring)".to_string()),
+                (sword, "Rusty Sword".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_items_with_the_same_name_keep_their_order() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        carry(&mut world, player, "Healing Potion");
+        carry(&mut world, player, "Healing Potion");
+        let before = carried_items(&world, player);
+
+        // Moving an item to another archetype changes the order legion visits them in.
+        world.entry(before[0].0).unwrap().add_component(Armor(0));
+
+        assert_eq!(carried_items(&world, player), before);
+    }
+
+    #[test]
+    fn test_letters_and_pages() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        for i in 0..30 {
+            carry(&mut world, player, &format!("Item {:02}", i));
+        }
+        let items = carried_items(&world, player);
+        let mut screen = InventoryScreen::default();
+        screen.open(InventoryMode::Use);
+
+        assert_eq!(
+            screen.item_for_key(&items, VirtualKeyCode::B),
+            Some(items[1].0)
+        );
+        assert_eq!(screen.page_items(&items).count(), ITEMS_PER_PAGE);
+
+        screen.turn_page(1, items.len());
+        assert_eq!(
+            screen.item_for_key(&items, VirtualKeyCode::B),
+            Some(items[27].0)
+        );
+        assert_eq!(screen.item_for_key(&items, VirtualKeyCode::E), None);
+
+        screen.turn_page(5, items.len());
+        assert_eq!(screen.page, 1);
+        assert_eq!(screen.item_for_key(&items, VirtualKeyCode::Key1), None);
+    }
+
+    #[test]
+    fn test_defence_counts_natural_and_equipped_armor() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        let ogre = world.push((Enemy, Armor(1)));
+        world.push((
+            Item,
+            Carried(player),
+            Armor(2),
+            Equipped(EquipmentSlot::Body),
+        ));
+        world.push((Item, Carried(player), Armor(1)));
+
+        assert_eq!(defence(&world, player), 2);
+        assert_eq!(defence(&world, ogre), 1);
+    }
+
+    #[test]
+    fn test_describe_item() {
+        let mut world = World::default();
+        let potion = world.push((Item, Provides(vec![ProvidedEffect::new("Healing", 6)])));
+        let sword = world.push((
+            Item,
+            Damage("1d4+1".parse().unwrap()),
+            Equippable(EquipmentSlot::MainHand),
+        ));
+
+        assert_eq!(
+            describe_item(&world, potion),
+            vec!["Heals up to 6 hp when drunk."]
+        );
+        assert_eq!(
+            describe_item(&world, sword),
+            vec![
+                "Adds 1d4+1 damage to your attacks while equipped.",
+                "It can be equipped in your main hand slot."
+            ]
+        );
+    }
+}
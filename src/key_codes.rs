@@ -0,0 +1,246 @@
+use crate::prelude::*;
+
+macro_rules! key_codes {
+    ($($key:ident),* $(,)?) => {
+        /// Parses the name `VirtualKeyCode`'s `Debug` output gives a key, e.g. `Up` or `Key1`.
+        pub fn parse_key_code(name: &str) -> Option<VirtualKeyCode> {
+            match name {
+                $(stringify!($key) => Some(VirtualKeyCode::$key),)*
+                _ => None,
+            }
+        }
+    };
+}
+
+key_codes!(
+    Key1,
+    Key2,
+    Key3,
+    Key4,
+    Key5,
+    Key6,
+    Key7,
+    Key8,
+    Key9,
+    Key0,
+    A,
+    B,
+    C,
+    D,
+    E,
+    F,
+    G,
+    H,
+    I,
+    J,
+    K,
+    L,
+    M,
+    N,
+    O,
+    P,
+    Q,
+    R,
+    S,
+    T,
+    U,
+    V,
+    W,
+    X,
+    Y,
+    Z,
+    Escape,
+    F1,
+    F2,
+    F3,
+    F4,
+    F5,
+    F6,
+    F7,
+    F8,
+    F9,
+    F10,
+    F11,
+    F12,
+    F13,
+    F14,
+    F15,
+    F16,
+    F17,
+    F18,
+    F19,
+    F20,
+    F21,
+    F22,
+    F23,
+    F24,
+    Snapshot,
+    Scroll,
+    Pause,
+    Insert,
+    Home,
+    Delete,
+    End,
+    PageDown,
+    PageUp,
+    Left,
+    Up,
+    Right,
+    Down,
+    Back,
+    Return,
+    Space,
+    Compose,
+    Caret,
+    Numlock,
+    Numpad0,
+    Numpad1,
+    Numpad2,
+    Numpad3,
+    Numpad4,
+    Numpad5,
+    Numpad6,
+    Numpad7,
+    Numpad8,
+    Numpad9,
+    NumpadAdd,
+    NumpadDivide,
+    NumpadDecimal,
+    NumpadComma,
+    NumpadEnter,
+    NumpadEquals,
+    NumpadMultiply,
+    NumpadSubtract,
+    AbntC1,
+    AbntC2,
+    Apostrophe,
+    Apps,
+    Asterisk,
+    At,
+    Ax,
+    Backslash,
+    Calculator,
+    Capital,
+    Colon,
+    Comma,
+    Convert,
+    Equals,
+    Grave,
+    Kana,
+    Kanji,
+    LAlt,
+    LBracket,
+    LControl,
+    LShift,
+    LWin,
+    Mail,
+    MediaSelect,
+    MediaStop,
+    Minus,
+    Mute,
+    MyComputer,
+    NavigateForward,
+    NavigateBackward,
+    NextTrack,
+    NoConvert,
+    OEM102,
+    Period,
+    PlayPause,
+    Plus,
+    Power,
+    PrevTrack,
+    RAlt,
+    RBracket,
+    RControl,
+    RShift,
+    RWin,
+    Semicolon,
+    Slash,
+    Sleep,
+    Stop,
+    Sysrq,
+    Tab,
+    Underline,
+    Unlabeled,
+    VolumeDown,
+    VolumeUp,
+    Wake,
+    WebBack,
+    WebFavorites,
+    WebForward,
+    WebHome,
+    WebRefresh,
+    WebSearch,
+    WebStop,
+    Yen,
+    Copy,
+    Paste,
+    Cut
+);
+
+/// Serializes an `Option<VirtualKeyCode>` by name, for use with `#[serde(with = "...")]`.
+pub mod key_code_serde {
+    use super::*;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        key: &Option<VirtualKeyCode>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        match key {
+            Some(key) => serializer.serialize_some(&format!("{:?}", key)),
+            None => serializer.serialize_none(),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<VirtualKeyCode>, D::Error> {
+        match Option::<String>::deserialize(deserializer)? {
+            Some(name) => parse_key_code(&name)
+                .map(Some)
+                .ok_or_else(|| de::Error::custom(format!("Unknown key \"{}\"", name))),
+            None => Ok(None),
+        }
+    }
+}
+
+/// Parses a comma separated list of key names, such as `Up,Up,G,Key1`.
+pub fn parse_key_codes(names: &str) -> Result<Vec<VirtualKeyCode>, String> {
+    names
+        .split(',')
+        .map(str::trim)
+        .filter(|name| !name.is_empty())
+        .map(|name| parse_key_code(name).ok_or_else(|| format!("Unknown key \"{}\"", name)))
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_names_round_trip() {
+        for key in [
+            VirtualKeyCode::Up,
+            VirtualKeyCode::Key1,
+            VirtualKeyCode::G,
+            VirtualKeyCode::NumpadEnter,
+        ] {
+            assert_eq!(parse_key_code(&format!("{:?}", key)), Some(key));
+        }
+    }
+
+    #[test]
+    fn test_parse_list() {
+        assert_eq!(
+            parse_key_codes("Up, Left,G"),
+            Ok(vec![
+                VirtualKeyCode::Up,
+                VirtualKeyCode::Left,
+                VirtualKeyCode::G
+            ])
+        );
+        assert!(parse_key_codes("Up,Sideways").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+/// A level the player has left, kept so it can be put back exactly as it was.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct StoredLevel {
+    pub map: Map,
+    pub theme: ThemeId,
+}
+
+/// Every visited level other than the current one. Their entities stay in the `World`, parked
+/// with a `StoredOnLevel` in place of their `Point` so no system picks them up.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct LevelStore {
+    pub levels: HashMap<usize, StoredLevel>,
+}
+
+impl LevelStore {
+    pub fn store(&mut self, level: usize, map: Map, theme: ThemeId) {
+        self.levels.insert(level, StoredLevel { map, theme });
+    }
+
+    pub fn take(&mut self, level: usize) -> Option<StoredLevel> {
+        self.levels.remove(&level)
+    }
+}
+
+/// Swaps the `Point` of everything on the current level, apart from the player, for a
+/// `StoredOnLevel`, and throws away any messages that haven't been handled yet.
+pub fn park_level(ecs: &mut World, resources: &mut Resources, level: usize) {
+    let mut commands = CommandBuffer::new(ecs);
+
+    for (entity, pos) in <(Entity, &Point)>::query()
+        .filter(!component::<Player>())
+        .iter(ecs)
+    {
+        commands.remove_component::<Point>(*entity);
+        commands.add_component(
+            *entity,
+            StoredOnLevel {
+                level,
+                position: *pos,
+            },
+        );
+    }
+
+    for entity in <Entity>::query()
+        .filter(
+            component::<WantsToMove>()
+                | component::<WantsToAttack>()
+                | component::<WantsToActivateItem>()
+                | component::<WantsToCloseDoor>(),
+        )
+        .iter(ecs)
+    {
+        commands.remove(*entity);
+    }
+
+    commands.flush(ecs, resources);
+}
+
+/// Puts back everything `park_level` stored for `level`, with their fields of view recalculated.
+pub fn unpark_level(ecs: &mut World, resources: &mut Resources, level: usize) {
+    let mut commands = CommandBuffer::new(ecs);
+
+    for (entity, stored, fov) in <(Entity, &StoredOnLevel, Option<&FieldOfView>)>::query()
+        .iter(ecs)
+        .filter(|(_, stored, _)| stored.level == level)
+    {
+        commands.remove_component::<StoredOnLevel>(*entity);
+        commands.add_component(*entity, stored.position);
+        if let Some(fov) = fov {
+            commands.add_component(*entity, fov.clone_dirty());
+        }
+    }
+
+    commands.flush(ecs, resources);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn player_pos(game: &Game) -> Point {
+        *<&Point>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .next()
+            .unwrap()
+    }
+
+    fn level_entities(game: &Game) -> Vec<(Name, Point)> {
+        let mut entities: Vec<(Name, Point)> = <(&Name, &Point)>::query()
+            .iter(&game.ecs)
+            .map(|(name, pos)| (name.clone(), *pos))
+            .collect();
+        entities.sort_by_key(|(name, pos)| (name.0.clone(), pos.x, pos.y));
+        entities
+    }
+
+    fn entities_on_level(game: &Game) -> Vec<Entity> {
+        <Entity>::query()
+            .filter(component::<Point>() & !component::<Player>())
+            .iter(&game.ecs)
+            .copied()
+            .collect()
+    }
+
+    fn take_stairs(game: &mut Game, turn_state: TurnState) {
+        game.resources.insert(turn_state);
+        game.tick(None, Point::zero());
+    }
+
+    #[test]
+    fn test_levels_persist() {
+        let mut game = Game::new().unwrap();
+        game.start(RunSeed(7));
+        {
+            let mut map = game.resources.get_mut::<Map>().unwrap();
+            map.revealed_tiles[0] = true;
+        }
+        let map = game.resources.get::<Map>().unwrap().clone();
+        let entities = level_entities(&game);
+        let level_zero = entities_on_level(&game);
+
+        take_stairs(&mut game, TurnState::NextLevel);
+        let level_one = game.resources.get::<Map>().unwrap().clone();
+        assert_ne!(level_one, map);
+        assert!(level_one.tiles.contains(&TileType::UpStairs));
+        assert!(level_entities(&game)
+            .iter()
+            .all(|entity| !entities.contains(entity)));
+        let on_level_one = entities_on_level(&game);
+        assert!(level_zero
+            .iter()
+            .all(|entity| !on_level_one.contains(entity)));
+
+        take_stairs(&mut game, TurnState::PreviousLevel);
+        assert_eq!(*game.resources.get::<Map>().unwrap(), map);
+        assert_eq!(level_entities(&game), entities);
+        let exit = map.find_tile(TileType::Exit).unwrap();
+        assert_eq!(
+            DistanceAlg::Pythagoras.distance2d(player_pos(&game), exit),
+            1.0
+        );
+
+        take_stairs(&mut game, TurnState::NextLevel);
+        assert_eq!(*game.resources.get::<Map>().unwrap(), level_one);
+        assert_eq!(game.turn_state(), TurnState::AwaitingInput);
+    }
+
+    #[test]
+    fn test_arriving_where_the_stairs_have_no_floor_beside_them() {
+        let mut game = Game::new().unwrap();
+        game.start(RunSeed(7));
+        {
+            let mut map = game.resources.get_mut::<Map>().unwrap();
+            let exit = map.find_tile(TileType::Exit).unwrap();
+            for direction in Map::directions() {
+                if map.try_tile(exit + direction) == Some(TileType::Floor) {
+                    let idx = map.point2d_to_index(exit + direction);
+                    map.tiles[idx] = TileType::DoorOpen;
+                }
+            }
+        }
+
+        take_stairs(&mut game, TurnState::NextLevel);
+        take_stairs(&mut game, TurnState::PreviousLevel);
+
+        let map = game.resources.get::<Map>().unwrap();
+        assert_eq!(map.try_tile(player_pos(&game)), Some(TileType::Floor));
+    }
+}
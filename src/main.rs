@@ -2,10 +2,25 @@
 
 mod camera;
 mod components;
+mod dice;
+mod effects;
+mod flow_field;
+mod game;
+mod headless;
+mod inventory;
+mod key_codes;
+mod level_store;
 mod map;
 mod map_builder;
+mod message_log;
+mod replay;
+mod resource_dir;
+mod run_seed;
+mod save_game;
+mod spatial_index;
 mod spawner;
 mod systems;
+mod targeting;
 mod turn_state;
 
 mod prelude {
@@ -17,6 +32,8 @@ mod prelude {
     pub const SCREEN_HEIGHT: i32 = 50;
     pub const DISPLAY_WIDTH: i32 = SCREEN_WIDTH / 2;
     pub const DISPLAY_HEIGHT: i32 = SCREEN_HEIGHT / 2;
+    pub const MAP_WIDTH: i32 = 80;
+    pub const MAP_HEIGHT: i32 = 50;
     pub const TILE_SIZE: i32 = 32;
     pub const LAYER_MAP: usize = 0;
     pub const LAYER_ENTITIES: usize = 1;
@@ -25,18 +42,59 @@ mod prelude {
     pub const UNREACHABLE: &f32 = &f32::MAX;
     pub use crate::camera::*;
     pub use crate::components::*;
+    pub use crate::dice::*;
+    pub use crate::effects::*;
+    pub use crate::flow_field::*;
+    pub use crate::game::*;
+    pub use crate::headless::*;
+    pub use crate::inventory::*;
+    pub use crate::key_codes::*;
+    pub use crate::level_store::*;
     pub use crate::map::*;
     pub use crate::map_builder::*;
+    pub use crate::message_log::*;
+    pub use crate::replay::*;
+    pub use crate::resource_dir::*;
+    pub use crate::run_seed::*;
+    pub use crate::save_game::*;
+    pub use crate::spatial_index::*;
     pub use crate::spawner::*;
     pub use crate::systems::*;
+    pub use crate::targeting::*;
     pub use crate::turn_state::*;
 }
 
-use std::collections::HashSet;
-
 use prelude::*;
 
 fn main() -> BError {
+    let args: Vec<String> = std::env::args().collect();
+    let seed = seed_from_args(&args);
+    let replay = arg_value(&args, "--replay").map(|path| {
+        Replay::load(path).unwrap_or_else(|err| {
+            eprintln!("Unable to load replay {}: {}", path, err);
+            std::process::exit(2);
+        })
+    });
+
+    if args.iter().any(|arg| arg == "--headless") {
+        match replay {
+            Some(replay) => {
+                let game = exit_on_error(replay.play());
+                let report = HeadlessReport::new(&game, replay.seed, 0);
+                println!(
+                    "replay of seed {}: {:?} on level {} after {} frames",
+                    report.seed.0,
+                    report.turn_state,
+                    report.map_level,
+                    replay.len()
+                );
+            }
+            None => run_headless_from_args(&args, seed),
+        }
+        return Ok(());
+    }
+
+    let game = exit_on_error(Game::new());
     let font = "dungeonfont.png";
     let terminal_font = "terminal8x8.png";
 
@@ -45,7 +103,7 @@ fn main() -> BError {
         .with_fps_cap(30.0)
         .with_dimensions(DISPLAY_WIDTH, DISPLAY_HEIGHT)
         .with_tile_dimensions(TILE_SIZE, TILE_SIZE)
-        .with_resource_path("resources")
+        .with_resource_path(resource_dir().display())
         .with_font(font, TILE_SIZE, TILE_SIZE)
         .with_font(terminal_font, 8, 8)
         .with_simple_console(DISPLAY_WIDTH, DISPLAY_HEIGHT, font)
@@ -53,71 +111,191 @@ fn main() -> BError {
         .with_simple_console_no_bg(SCREEN_WIDTH * 2, SCREEN_HEIGHT * 2, terminal_font)
         .build()?;
 
-    let mut state = State::new();
-    state.start();
+    // Lets `tick` see the window closing so the run can be saved first.
+    INPUT.lock().activate_event_queue();
+
+    let state = match replay {
+        Some(replay) => State::playback(replay, game),
+        None => State::new(seed, game),
+    };
     main_loop(context, state)
 }
 
+/// The game can't run without its data files, or play back a replay it can't start, so a
+/// problem with either ends it here.
+fn exit_on_error<T, E: std::fmt::Display>(result: Result<T, E>) -> T {
+    result.unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    })
+}
+
+fn arg_value<'a>(args: &'a [String], flag: &str) -> Option<&'a str> {
+    args.iter()
+        .position(|arg| arg == flag)
+        .map(|i| args.get(i + 1).map(String::as_str).unwrap_or(""))
+}
+
+fn seed_from_args(args: &[String]) -> Option<u64> {
+    arg_value(args, "--seed").map(|seed| seed.parse().expect("--seed expects a number"))
+}
+
+/// `--headless [--seed N] [--runs N] [--keys Up,Up,G,...]` plays each run without a window and
+/// prints how it ended.
+fn run_headless_from_args(args: &[String], seed: Option<u64>) {
+    let keys = parse_key_codes(arg_value(args, "--keys").unwrap_or("")).unwrap_or_else(|err| {
+        eprintln!("{}", err);
+        std::process::exit(2);
+    });
+    let runs: u64 = arg_value(args, "--runs")
+        .map(|runs| runs.parse().expect("--runs expects a number"))
+        .unwrap_or(1);
+    let first_seed = seed.map(RunSeed).unwrap_or_else(RunSeed::random);
+
+    for run in 0..runs {
+        let report = exit_on_error(run_headless(RunSeed(first_seed.0.wrapping_add(run)), &keys));
+        println!(
+            "seed {}: {:?} on level {} after {} keys",
+            report.seed.0, report.turn_state, report.map_level, report.keys_used
+        );
+    }
+}
+
 struct State {
-    ecs: World,
-    resources: Resources,
-    input_systems: Schedule,
-    player_systems: Schedule,
-    monster_systems: Schedule,
+    seed: Option<u64>,
+    game: Game,
+    render_systems: Schedule,
+    recording: Option<Replay>,
+    playback: Option<Playback>,
 }
 
 impl State {
-    fn new() -> Self {
-        let ecs = World::default();
-        let resources = Resources::default();
-        // These get recreated immediately on start, so they should probably just be optional.
-
+    fn new(seed: Option<u64>, game: Game) -> Self {
         Self {
-            ecs,
-            resources,
-            input_systems: build_input_scheduler(),
-            player_systems: build_player_scheduler(),
-            monster_systems: build_monster_scheduler(),
+            seed,
+            game,
+            render_systems: build_render_scheduler(),
+            recording: None,
+            playback: None,
         }
     }
 
-    fn start(&mut self) {
-        let mut rng = RandomNumberGenerator::new();
-        let mut map_builder = MapBuilder::new().build(&mut rng);
-        let mut ecs = World::default();
-        let mut resources = Resources::default();
-
-        let exit_idx = map_builder.map.point2d_to_index(map_builder.amulet_start);
-        map_builder.map.tiles[exit_idx] = TileType::Exit;
-        spawn_player(&mut ecs, map_builder.player_start);
-        // spawn_amulet_of_yala(&mut ecs, map_builder.amulet_start);
-
-        State::spawn_level(
-            &mut ecs,
-            &mut self.resources,
-            &mut rng,
-            0,
-            &map_builder.monster_spawns,
-        );
+    fn playback(replay: Replay, game: Game) -> Self {
+        let mut state = State::new(Some(replay.seed.0), game);
+        exit_on_error(replay.start_game(&mut state.game));
+        state.playback = Some(Playback::new(&replay));
+        state
+    }
+
+    fn start_new_game(&mut self) {
+        let seed = self.seed.map(RunSeed).unwrap_or_else(RunSeed::random);
+        self.game.start(seed);
+        self.recording = Some(Replay::new(seed));
+        self.playback = None;
+    }
+
+    /// Replays are written as each key is recorded, so a crash still leaves one behind.
+    fn save_recording(&self) {
+        if let Some(recording) = &self.recording {
+            if let Err(err) = recording.save(REPLAY_FILE) {
+                println!("Unable to save {}: {}", REPLAY_FILE, err);
+            }
+        }
+    }
 
-        resources.insert(map_builder.map);
-        resources.insert(Camera::new(map_builder.player_start));
-        resources.insert(TurnState::AwaitingInput);
-        resources.insert(map_builder.theme);
+    fn play(&mut self, ctx: &mut BTerm) {
+        if let Some(playback) = &mut self.playback {
+            playback.advance(&mut self.game, ctx.key);
+            return;
+        }
+
+        let mouse_pos = Point::from_tuple(ctx.mouse_pos());
+        if let Some(recording) = &mut self.recording {
+            recording.record(ctx.key, mouse_pos);
+        }
+        self.game.tick(ctx.key, mouse_pos);
 
-        self.ecs = ecs;
-        self.resources = resources;
+        if ctx.key.is_some() || !self.game.is_in_progress() {
+            self.save_recording();
+        }
+        // A finished run can't be continued, so its save goes as soon as it ends.
+        if !self.game.is_in_progress() {
+            if let Err(err) = delete_save() {
+                println!("Unable to delete {}: {}", SAVE_FILE, err);
+            }
+        }
     }
 
-    pub fn spawn_level(
-        ecs: &mut World,
-        resources: &mut Resources,
-        rng: &mut RandomNumberGenerator,
-        level: usize,
-        spawn_points: &[Point],
-    ) {
-        let template = Templates::load();
-        template.spawn_entities(ecs, resources, rng, level, spawn_points);
+    fn playback_status(&self, ctx: &mut BTerm) {
+        if let Some(playback) = &self.playback {
+            let (position, length) = playback.progress();
+            let status = if playback.is_finished() {
+                "finished".to_string()
+            } else if playback.paused {
+                "paused".to_string()
+            } else {
+                format!("x{}", playback.speed())
+            };
+
+            ctx.set_active_console(LAYER_HUD);
+            ctx.print_color(
+                1,
+                SCREEN_HEIGHT * 2 - 2,
+                YELLOW,
+                BLACK,
+                format!(
+                    "Replay {}/{} {} - Space: pause, +/-: speed, .: step",
+                    position, length, status
+                ),
+            );
+        }
+    }
+
+    fn resume(&mut self) {
+        match load_game() {
+            Ok((ecs, resources, save)) => {
+                self.game.load(ecs, resources);
+                let seed = *self.game.resources.get::<RunSeed>().unwrap();
+                self.recording = Some(Replay::resumed(seed, save));
+                self.playback = None;
+            }
+            Err(err) => {
+                println!("Unable to load {}: {}", SAVE_FILE, err);
+                self.start_new_game();
+            }
+        }
+    }
+
+    fn main_menu(&mut self, ctx: &mut BTerm) {
+        let has_save = save_exists();
+
+        ctx.set_active_console(LAYER_HUD);
+        ctx.print_color_centered(2, YELLOW, BLACK, "Rusty Rogue");
+        if has_save {
+            ctx.print_color_centered(5, GREEN, BLACK, "Press 1 to continue your journey.");
+        }
+        ctx.print_color_centered(6, WHITE, BLACK, "Press 2 to start a new game.");
+
+        match ctx.key {
+            Some(VirtualKeyCode::Key1) if has_save => self.resume(),
+            Some(VirtualKeyCode::Key2) => self.start_new_game(),
+            _ => {}
+        }
+    }
+
+    fn handle_events(&mut self, ctx: &mut BTerm) {
+        let mut input = INPUT.lock();
+        while let Some(event) = input.pop() {
+            if event == BEvent::CloseRequested {
+                self.save_recording();
+                if self.game.is_in_progress() && self.playback.is_none() {
+                    if let Err(err) = save_game(&self.game.ecs, &self.game.resources) {
+                        println!("Unable to save {}: {}", SAVE_FILE, err);
+                    }
+                }
+                ctx.quit();
+            }
+        }
     }
 
     fn game_over(&mut self, ctx: &mut BTerm) {
@@ -145,7 +323,7 @@ impl State {
         ctx.print_color_centered(9, GREEN, BLACK, "Press 1 to play again.");
 
         if let Some(VirtualKeyCode::Key1) = ctx.key {
-            self.start();
+            self.start_new_game();
         }
     }
 
@@ -166,97 +344,32 @@ impl State {
         );
         ctx.print_color_centered(7, GREEN, BLACK, "Press 1 to play again.");
         if let Some(VirtualKeyCode::Key1) = ctx.key {
-            self.start();
-        }
-    }
-
-    fn advance_level(&mut self) {
-        let player = *<Entity>::query()
-            .filter(component::<Player>())
-            .iter(&self.ecs)
-            .next()
-            .unwrap();
-
-        let mut entities_to_keep = HashSet::new();
-        entities_to_keep.insert(player);
-
-        for item in <(Entity, &Carried)>::query()
-            .iter(&self.ecs)
-            .filter(|(_e, carried)| carried.0 == player)
-            .map(|(&e, _carried)| e)
-        {
-            entities_to_keep.insert(item);
-        }
-
-        let mut cb = CommandBuffer::new(&self.ecs);
-
-        for e in Entity::query().iter(&self.ecs) {
-            if !entities_to_keep.contains(e) {
-                cb.remove(*e);
-            }
-        }
-        cb.flush(&mut self.ecs, &mut self.resources);
-
-        <&mut FieldOfView>::query()
-            .iter_mut(&mut self.ecs)
-            .for_each(|fov| fov.is_dirty = true);
-
-        let mut map_level: usize = 0;
-
-        let mut rng = RandomNumberGenerator::new();
-        let mut map_builder = MapBuilder::new().build(&mut rng);
-        for (player, pos) in <(&mut Player, &mut Point)>::query().iter_mut(&mut self.ecs) {
-            player.map_level += 1;
-            map_level = player.map_level;
-            pos.x = map_builder.player_start.x;
-            pos.y = map_builder.player_start.y;
-        }
-
-        if map_level == 2 {
-            spawn_amulet_of_yala(&mut self.ecs, map_builder.amulet_start);
-        } else {
-            let exit_idx = map_builder.map.point2d_to_index(map_builder.amulet_start);
-            map_builder.map.tiles[exit_idx] = TileType::Exit;
+            self.start_new_game();
         }
-
-        State::spawn_level(
-            &mut self.ecs,
-            &mut self.resources,
-            &mut rng,
-            map_level,
-            &map_builder.monster_spawns,
-        );
-
-        self.resources.insert(map_builder.map);
-        self.resources.insert(Camera::new(map_builder.player_start));
-        self.resources.insert(TurnState::AwaitingInput);
-        self.resources.insert(map_builder.theme);
     }
 }
 
 impl GameState for State {
     fn tick(&mut self, ctx: &mut BTerm) {
         self.clear_console(ctx);
+        self.handle_events(ctx);
 
-        self.resources.insert(ctx.key);
-        self.resources.insert(Point::from_tuple(ctx.mouse_pos()));
-
-        let current_state = *self.resources.get::<TurnState>().unwrap();
-
-        match current_state {
-            TurnState::AwaitingInput => self
-                .input_systems
-                .execute(&mut self.ecs, &mut self.resources),
-            TurnState::PlayerTurn => self
-                .player_systems
-                .execute(&mut self.ecs, &mut self.resources),
-            TurnState::MonsterTurn => self
-                .monster_systems
-                .execute(&mut self.ecs, &mut self.resources),
+        match self.game.turn_state() {
+            TurnState::MainMenu => self.main_menu(ctx),
             TurnState::GameOver => self.game_over(ctx),
             TurnState::Victory => self.victory(ctx),
-            TurnState::NextLevel => self.advance_level(),
+            _ => {
+                self.play(ctx);
+                for err in self.game.take_placement_errors() {
+                    eprintln!("{}", err);
+                }
+                if self.game.is_in_progress() {
+                    self.render_systems
+                        .execute(&mut self.game.ecs, &mut self.game.resources);
+                }
+            }
         }
+        self.playback_status(ctx);
 
         render_draw_buffer(ctx).expect("Render Error");
     }
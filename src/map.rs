@@ -1,52 +1,68 @@
 use crate::prelude::*;
+use serde::{Deserialize, Serialize};
 
-pub const NUM_TILES: usize = (SCREEN_WIDTH * SCREEN_HEIGHT) as usize;
-
-#[derive(Copy, Clone, PartialEq)]
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
     Exit,
+    DoorClosed,
+    DoorOpen,
+    UpStairs,
 }
 
+/// Closed doors count as enterable so paths run through them; walking into one opens it.
 fn is_enterable_tile(tile: TileType) -> bool {
     match tile {
         TileType::Wall => false,
         TileType::Floor => true,
         TileType::Exit => true,
+        TileType::DoorClosed => true,
+        TileType::DoorOpen => true,
+        TileType::UpStairs => true,
     }
 }
 
-pub fn map_idx(x: i32, y: i32) -> usize {
-    ((y * SCREEN_WIDTH) + x) as usize
-}
-
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub struct Map {
+    pub width: i32,
+    pub height: i32,
     pub tiles: Vec<TileType>,
     pub revealed_tiles: Vec<bool>,
 }
 
 impl Map {
-    pub fn new() -> Self {
+    pub fn new(width: i32, height: i32) -> Self {
+        let num_tiles = (width * height) as usize;
         Self {
-            tiles: vec![TileType::Floor; NUM_TILES],
-            revealed_tiles: vec![false; NUM_TILES],
+            width,
+            height,
+            tiles: vec![TileType::Floor; num_tiles],
+            revealed_tiles: vec![false; num_tiles],
         }
     }
 
+    pub fn idx(&self, x: i32, y: i32) -> usize {
+        ((y * self.width) + x) as usize
+    }
+
+    pub fn center(&self) -> Point {
+        Point::new(self.width / 2, self.height / 2)
+    }
+
     pub fn in_bounds(&self, point: Point) -> bool {
-        point.x >= 0 && point.x < SCREEN_WIDTH && point.y >= 0 && point.y < SCREEN_HEIGHT
+        point.x >= 0 && point.x < self.width && point.y >= 0 && point.y < self.height
     }
 
     pub fn can_enter_tile(&self, point: Point) -> bool {
-        self.in_bounds(point) && is_enterable_tile(self.tiles[map_idx(point.x, point.y)])
+        self.in_bounds(point) && is_enterable_tile(self.tiles[self.idx(point.x, point.y)])
     }
 
     pub fn try_idx(&self, point: Point) -> Option<usize> {
         if !self.in_bounds(point) {
             None
         } else {
-            Some(map_idx(point.x, point.y))
+            Some(self.idx(point.x, point.y))
         }
     }
 
@@ -71,6 +87,42 @@ impl Map {
     pub fn try_tile(&self, pos: Point) -> Option<TileType> {
         Some(self.tiles[self.try_idx(pos)?])
     }
+
+    pub fn find_tile(&self, tile: TileType) -> Option<Point> {
+        self.tiles
+            .iter()
+            .position(|&t| t == tile)
+            .map(|idx| self.index_to_point2d(idx))
+    }
+
+    /// The closest plain floor tile that can be walked to from `pos`, for arriving beside a
+    /// staircase rather than on it, where any key would take the stairs straight back.
+    pub fn nearest_floor(&self, pos: Point) -> Option<Point> {
+        let beside = Map::directions()
+            .into_iter()
+            .map(|direction| pos + direction)
+            .find(|&pt| self.try_tile(pt) == Some(TileType::Floor));
+        if beside.is_some() {
+            return beside;
+        }
+
+        let distances = DijkstraMap::new(
+            self.width,
+            self.height,
+            &[self.try_idx(pos)?],
+            self,
+            MAX_FLOWMAP_DISTANCE,
+        );
+        self.tiles
+            .iter()
+            .zip(distances.map.iter())
+            .enumerate()
+            .filter(|(_, (&tile, &distance))| {
+                tile == TileType::Floor && distance < MAX_FLOWMAP_DISTANCE
+            })
+            .min_by(|(_, (_, a)), (_, (_, b))| a.total_cmp(b))
+            .map(|(idx, _)| self.index_to_point2d(idx))
+    }
 }
 
 impl BaseMap for Map {
@@ -92,13 +144,13 @@ impl BaseMap for Map {
     }
 
     fn is_opaque(&self, idx: usize) -> bool {
-        self.tiles[idx] != TileType::Floor
+        !matches!(self.tiles[idx], TileType::Floor | TileType::DoorOpen)
     }
 }
 
 impl Algorithm2D for Map {
     fn dimensions(&self) -> Point {
-        Point::new(SCREEN_WIDTH, SCREEN_HEIGHT)
+        Point::new(self.width, self.height)
     }
 
     fn in_bounds(&self, pos: Point) -> bool {
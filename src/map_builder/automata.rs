@@ -5,8 +5,8 @@ use super::MapArchitect;
 pub struct CellularAutomataArchitect {}
 
 impl MapArchitect for CellularAutomataArchitect {
-    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder {
-        let mut mb = MapBuilder::new();
+    fn build(&mut self, width: i32, height: i32, rng: &mut RandomNumberGenerator) -> MapBuilder {
+        let mut mb = MapBuilder::new(width, height);
         self.random_noise_map(rng, &mut mb.map);
 
         for _ in 0..10 {
@@ -38,7 +38,7 @@ impl CellularAutomataArchitect {
 
         for iy in -1..=1 {
             for ix in -1..=1 {
-                if !(ix == 0 && iy == 0) && map.tiles[map_idx(x + ix, y + iy)] == TileType::Wall {
+                if !(ix == 0 && iy == 0) && map.tiles[map.idx(x + ix, y + iy)] == TileType::Wall {
                     neighbors += 1
                 }
             }
@@ -49,10 +49,10 @@ impl CellularAutomataArchitect {
 
     fn iteration(&mut self, map: &mut Map) {
         let mut new_tiles = map.tiles.clone();
-        for y in 1..SCREEN_HEIGHT - 1 {
-            for x in 1..SCREEN_WIDTH - 1 {
+        for y in 1..map.height - 1 {
+            for x in 1..map.width - 1 {
                 let neighbors = self.count_neighbors(x, y, map);
-                let idx = map_idx(x, y);
+                let idx = map.idx(x, y);
                 if neighbors > 4 || neighbors == 0 {
                     new_tiles[idx] = TileType::Wall;
                 } else {
@@ -65,7 +65,7 @@ impl CellularAutomataArchitect {
     }
 
     fn find_start(&self, map: &Map) -> Point {
-        let center = Point::new(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2);
+        let center = map.center();
         let closest_point = map
             .tiles
             .iter()
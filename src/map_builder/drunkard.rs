@@ -3,16 +3,16 @@ use crate::prelude::*;
 use super::MapArchitect;
 
 const STAGGER_DISTANCE: usize = 400;
-const DESIRED_FLOOR: usize = NUM_TILES / 3;
 
 pub struct DrunkardsWalkArchitect {}
 
 impl MapArchitect for DrunkardsWalkArchitect {
-    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder {
-        let mut mb = MapBuilder::new();
+    fn build(&mut self, width: i32, height: i32, rng: &mut RandomNumberGenerator) -> MapBuilder {
+        let mut mb = MapBuilder::new(width, height);
+        let desired_floor = mb.map.tiles.len() / 3;
 
         mb.fill(TileType::Wall);
-        let center = Point::new(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2);
+        let center = mb.map.center();
         self.drunkard(&center, rng, &mut mb.map);
 
         while mb
@@ -21,17 +21,17 @@ impl MapArchitect for DrunkardsWalkArchitect {
             .iter()
             .filter(|&&tile| tile == TileType::Floor)
             .count()
-            < DESIRED_FLOOR
+            < desired_floor
         {
             self.drunkard(
-                &Point::new(rng.range(0, SCREEN_WIDTH), rng.range(0, SCREEN_HEIGHT)),
+                &Point::new(rng.range(0, width), rng.range(0, height)),
                 rng,
                 &mut mb.map,
             );
 
             let flow_map = DijkstraMap::new(
-                SCREEN_WIDTH,
-                SCREEN_HEIGHT,
+                width,
+                height,
                 &[mb.map.point2d_to_index(center)],
                 &mb.map,
                 MAX_FLOWMAP_DISTANCE,
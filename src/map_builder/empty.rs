@@ -5,17 +5,15 @@ use super::MapArchitect;
 pub struct EmptyArchitect {}
 
 impl MapArchitect for EmptyArchitect {
-    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder {
-        let mut mb = MapBuilder::new();
+    fn build(&mut self, width: i32, height: i32, rng: &mut RandomNumberGenerator) -> MapBuilder {
+        let mut mb = MapBuilder::new(width, height);
 
         mb.fill(TileType::Floor);
-        mb.player_start = Point::new(SCREEN_WIDTH / 2, SCREEN_HEIGHT / 2);
+        mb.player_start = mb.map.center();
         mb.amulet_start = mb.find_most_distant();
         for _ in 0..50 {
-            mb.monster_spawns.push(Point::new(
-                rng.range(1, SCREEN_WIDTH),
-                rng.range(1, SCREEN_HEIGHT),
-            ))
+            mb.monster_spawns
+                .push(Point::new(rng.range(1, width), rng.range(1, height)))
         }
 
         mb
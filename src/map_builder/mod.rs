@@ -1,13 +1,18 @@
 use crate::prelude::*;
+use serde::Deserialize;
 
 use self::{
     automata::CellularAutomataArchitect,
     drunkard::DrunkardsWalkArchitect,
-    prefab::{apply_prefab, FORTRESS},
+    empty::EmptyArchitect,
+    prefab::apply_prefab,
     rooms::RoomsArchitect,
     themes::{DungeonTheme, ForestTheme},
 };
 
+pub use self::prefab::{PlacementError, PrefabError, Prefabs};
+pub use self::themes::ThemeId;
+
 pub mod automata;
 pub mod drunkard;
 pub mod empty;
@@ -16,12 +21,36 @@ mod rooms;
 mod themes;
 
 const NUM_ROOMS: usize = 20;
+const MAX_ROOM_ATTEMPTS: usize = 1000;
+/// Room and monster counts were tuned for an 80x50 map, and are scaled by area from there.
+const TILES_PER_DEFAULT_MAP: usize = 80 * 50;
 
 pub trait MapArchitect {
-    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder;
+    fn build(&mut self, width: i32, height: i32, rng: &mut RandomNumberGenerator) -> MapBuilder;
+}
+
+/// Names each `MapArchitect`, so prefabs can say which kinds of level they fit in.
+#[derive(Clone, Copy, Debug, PartialEq, Deserialize)]
+pub enum ArchitectKind {
+    Empty,
+    Rooms,
+    DrunkardsWalk,
+    CellularAutomata,
+}
+
+impl ArchitectKind {
+    pub fn architect(self) -> Box<dyn MapArchitect> {
+        match self {
+            ArchitectKind::Empty => Box::new(EmptyArchitect {}),
+            ArchitectKind::Rooms => Box::new(RoomsArchitect {}),
+            ArchitectKind::DrunkardsWalk => Box::new(DrunkardsWalkArchitect {}),
+            ArchitectKind::CellularAutomata => Box::new(CellularAutomataArchitect {}),
+        }
+    }
 }
 
 pub trait MapTheme: Sync + Send {
+    fn id(&self) -> ThemeId;
     fn tile_to_render(&self, tile_type: TileType) -> FontCharType;
 }
 
@@ -29,32 +58,48 @@ pub struct MapBuilder {
     pub map: Map,
     pub rooms: Vec<Rect>,
     pub monster_spawns: Vec<Point>,
+    /// Random items a prefab guarantees, regardless of what the level's spawn table rolls.
+    pub item_spawns: Vec<Point>,
+    /// Specific templates a prefab placed by name.
+    pub template_spawns: Vec<(Point, String)>,
+    /// The areas covered by prefabs, which later prefabs must keep clear of.
+    pub vaults: Vec<Rect>,
+    /// Prefabs `build` chose for the level but couldn't find room for.
+    pub placement_errors: Vec<PlacementError>,
     pub player_start: Point,
     pub amulet_start: Point,
     pub theme: Box<dyn MapTheme>,
 }
 
 impl MapBuilder {
-    pub fn new() -> Self {
+    pub fn new(width: i32, height: i32) -> Self {
         Self {
-            map: Map::new(),
+            map: Map::new(width, height),
             rooms: Vec::new(),
             monster_spawns: Vec::new(),
+            item_spawns: Vec::new(),
+            template_spawns: Vec::new(),
+            vaults: Vec::new(),
+            placement_errors: Vec::new(),
             player_start: Point::zero(),
             amulet_start: Point::zero(),
             theme: Box::new(DungeonTheme {}),
         }
     }
 
-    pub fn build(self, rng: &mut RandomNumberGenerator) -> Self {
-        let mut architect: Box<dyn MapArchitect> = match rng.range(0, 3) {
-            0 => Box::new(DrunkardsWalkArchitect {}),
-            1 => Box::new(RoomsArchitect {}),
-            _ => Box::new(CellularAutomataArchitect {}),
+    pub fn build(self, level: usize, prefabs: &Prefabs, rng: &mut RandomNumberGenerator) -> Self {
+        let kind = match rng.range(0, 3) {
+            0 => ArchitectKind::DrunkardsWalk,
+            1 => ArchitectKind::Rooms,
+            _ => ArchitectKind::CellularAutomata,
         };
 
-        let mut mb = architect.build(rng);
-        apply_prefab(&mut mb, FORTRESS, rng);
+        let mut mb = kind.architect().build(self.map.width, self.map.height, rng);
+        for prefab in prefabs.choose(level, kind, rng) {
+            if let Err(err) = apply_prefab(&mut mb, prefab, rng) {
+                mb.placement_errors.push(err);
+            }
+        }
 
         mb.theme = match rng.range(0, 2) {
             0 => Box::new(DungeonTheme {}),
@@ -70,8 +115,8 @@ impl MapBuilder {
 
     fn find_most_distant(&self) -> Point {
         let flow_map = DijkstraMap::new(
-            SCREEN_WIDTH,
-            SCREEN_HEIGHT,
+            self.map.width,
+            self.map.height,
             &[self.map.point2d_to_index(self.player_start)],
             &self.map,
             MAX_FLOWMAP_DISTANCE,
@@ -90,12 +135,20 @@ impl MapBuilder {
     }
 
     fn build_random_rooms(&mut self, rng: &mut RandomNumberGenerator) {
-        while self.rooms.len() < NUM_ROOMS {
+        let num_rooms = usize::max(2, NUM_ROOMS * self.map.tiles.len() / TILES_PER_DEFAULT_MAP);
+        let max_room_size = i32::max(
+            3,
+            i32::min(10, i32::min(self.map.width, self.map.height) / 3),
+        );
+        let mut attempts = 0;
+
+        while self.rooms.len() < num_rooms && attempts < MAX_ROOM_ATTEMPTS {
+            attempts += 1;
             let room = Rect::with_size(
-                rng.range(1, SCREEN_WIDTH - 10),
-                rng.range(1, SCREEN_HEIGHT - 10),
-                rng.range(2, 10),
-                rng.range(2, 10),
+                rng.range(1, i32::max(2, self.map.width - max_room_size)),
+                rng.range(1, i32::max(2, self.map.height - max_room_size)),
+                rng.range(2, max_room_size),
+                rng.range(2, max_room_size),
             );
 
             let mut overlap = false;
@@ -137,7 +190,7 @@ impl MapBuilder {
 
     fn build_corridors(&mut self, rng: &mut RandomNumberGenerator) {
         let mut rooms = self.rooms.clone();
-        rooms.sort_by(|a, b| a.center().x.cmp(&b.center().x));
+        rooms.sort_by_key(|room| room.center().x);
 
         for (i, room) in rooms.iter().enumerate().skip(1) {
             let prev = rooms[i - 1].center();
@@ -153,9 +206,39 @@ impl MapBuilder {
         }
     }
 
+    /// Closes off every spot where a one tile wide corridor runs into the edge of a room.
+    fn build_doors(&mut self) {
+        let is_wall = |map: &Map, pt: Point| map.try_tile(pt) == Some(TileType::Wall);
+        let mut doors = Vec::new();
+
+        for room in self.rooms.iter() {
+            let above_and_below = (room.x1..room.x2)
+                .flat_map(|x| [Point::new(x, room.y1 - 1), Point::new(x, room.y2)])
+                .map(|pt| (pt, Point::new(1, 0)));
+            let left_and_right = (room.y1..room.y2)
+                .flat_map(|y| [Point::new(room.x1 - 1, y), Point::new(room.x2, y)])
+                .map(|pt| (pt, Point::new(0, 1)));
+
+            for (pt, along_wall) in above_and_below.chain(left_and_right) {
+                if self.map.try_tile(pt) == Some(TileType::Floor)
+                    && is_wall(&self.map, pt - along_wall)
+                    && is_wall(&self.map, pt + along_wall)
+                {
+                    doors.push(pt);
+                }
+            }
+        }
+
+        for door in doors {
+            let idx = self.map.point2d_to_index(door);
+            self.map.tiles[idx] = TileType::DoorClosed;
+        }
+    }
+
     fn spawn_monsters(&self, start: &Point, rng: &mut RandomNumberGenerator) -> Vec<Point> {
         const NUM_MONSTERS: usize = 50;
         const MIN_SPAWN_DISTANCE: f32 = 10.0;
+        let num_monsters = NUM_MONSTERS * self.map.tiles.len() / TILES_PER_DEFAULT_MAP;
         let mut spawnable_tiles: Vec<Point> = self
             .map
             .tiles
@@ -171,7 +254,7 @@ impl MapBuilder {
 
         let mut spawns = Vec::new();
 
-        for _ in 0..NUM_MONSTERS {
+        for _ in 0..usize::min(num_monsters, spawnable_tiles.len()) {
             let target_index = rng.random_slice_index(&spawnable_tiles).unwrap();
             spawns.push(spawnable_tiles[target_index]);
             spawnable_tiles.remove(target_index);
@@ -180,3 +263,71 @@ impl MapBuilder {
         spawns
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn architects() -> Vec<Box<dyn MapArchitect>> {
+        [
+            ArchitectKind::Empty,
+            ArchitectKind::Rooms,
+            ArchitectKind::DrunkardsWalk,
+            ArchitectKind::CellularAutomata,
+        ]
+        .into_iter()
+        .map(ArchitectKind::architect)
+        .collect()
+    }
+
+    #[test]
+    fn test_architects_build_any_size() {
+        for (width, height) in [(200, 120), (30, 20)] {
+            for mut architect in architects() {
+                let mut rng = RandomNumberGenerator::seeded(5);
+                let mb = architect.build(width, height, &mut rng);
+
+                assert_eq!(mb.map.width, width);
+                assert_eq!(mb.map.tiles.len(), (width * height) as usize);
+                assert!(mb.map.in_bounds(mb.player_start));
+                assert!(mb.map.in_bounds(mb.amulet_start));
+                assert!(mb
+                    .monster_spawns
+                    .iter()
+                    .all(|spawn| mb.map.in_bounds(*spawn)));
+            }
+        }
+    }
+
+    #[test]
+    fn test_build_keeps_size() {
+        let mut rng = RandomNumberGenerator::seeded(5);
+        let mb = MapBuilder::new(30, 20).build(0, &Prefabs::load().unwrap(), &mut rng);
+
+        assert_eq!(mb.map.dimensions(), Point::new(30, 20));
+        assert_eq!(mb.map.idx(29, 19), 30 * 20 - 1);
+    }
+
+    #[test]
+    fn test_rooms_get_doors_where_corridors_meet_them() {
+        let mut rng = RandomNumberGenerator::seeded(5);
+        let mb = RoomsArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
+        let doors: Vec<Point> = (0..mb.map.tiles.len())
+            .filter(|&idx| mb.map.tiles[idx] == TileType::DoorClosed)
+            .map(|idx| mb.map.index_to_point2d(idx))
+            .collect();
+        let is_wall = |pt: Point| mb.map.try_tile(pt) == Some(TileType::Wall);
+
+        assert!(!doors.is_empty());
+        for door in doors {
+            let horizontal = is_wall(door + Point::new(1, 0)) && is_wall(door - Point::new(1, 0));
+            let vertical = is_wall(door + Point::new(0, 1)) && is_wall(door - Point::new(0, 1));
+            assert!(horizontal || vertical);
+            assert!(mb.rooms.iter().any(|room| {
+                Map::directions()
+                    .into_iter()
+                    .any(|direction| room.point_in_rect(door + direction))
+            }));
+        }
+    }
+}
@@ -1,109 +1,486 @@
-use std::collections::btree_map::Range;
+use std::{
+    collections::{HashMap, HashSet},
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 
-use crate::prelude::*;
+use serde::Deserialize;
 
-pub const FORTRESS: &str = "
-------------
----######---
----#----#---
----#-M--#---
--###----###-
---M------M--
--###----###-
----#----#---
----#----#---
----######---
-------------
-";
+use crate::prelude::*;
 
+/// The prefab directory, inside `resource_dir()`.
+pub const PREFAB_DIR: &str = "prefabs";
 const MAX_ATTEMPTS: i32 = 10;
 const MIN_DISTANCE_FROM_PLAYER: f32 = 20.0;
+const MAX_PREFABS_PER_LEVEL: i32 = 3;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum PrefabTile {
+    /// Floor that random spawns may still land on.
+    Floor,
+    /// Floor that random spawns are kept off, so the vault holds exactly what it lists.
+    NoSpawn,
+    Wall,
+    /// A random monster or item for the level.
+    Monster,
+    /// A random item for the level.
+    Item,
+    /// The named template from the prefab's legend.
+    Template(String),
+    PlayerStart,
+    /// Where the level's exit, or the amulet on the last level, is placed.
+    Exit,
+}
+
+/// How a prefab is turned before it's placed: mirrored left to right, then rotated clockwise.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Orientation {
+    pub quarter_turns: u8,
+    pub mirrored: bool,
+}
+
+impl Orientation {
+    pub fn random(rng: &mut RandomNumberGenerator) -> Self {
+        Self {
+            quarter_turns: rng.range(0, 4) as u8,
+            mirrored: rng.range(0, 2) == 1,
+        }
+    }
+}
+
+impl PrefabTile {
+    const BUILT_IN: [char; 7] = ['-', '.', '#', 'M', '$', '@', '>'];
+
+    fn parse(c: char, legend: &HashMap<char, String>) -> Option<Self> {
+        match c {
+            '-' => Some(PrefabTile::Floor),
+            '.' => Some(PrefabTile::NoSpawn),
+            '#' => Some(PrefabTile::Wall),
+            'M' => Some(PrefabTile::Monster),
+            '$' => Some(PrefabTile::Item),
+            '@' => Some(PrefabTile::PlayerStart),
+            '>' => Some(PrefabTile::Exit),
+            _ => legend.get(&c).cloned().map(PrefabTile::Template),
+        }
+    }
+}
+
+/// A prefab file as it's written in `resources/prefabs`, before its layout is parsed.
+#[derive(Clone, Deserialize, Debug)]
+#[serde(rename = "Prefab")]
+struct PrefabFile {
+    weight: i32,
+    levels: HashSet<usize>,
+    #[serde(default)]
+    architects: Vec<ArchitectKind>,
+    #[serde(default = "PrefabFile::default_min_distance")]
+    min_distance_from_player: f32,
+    /// Extra layout characters, each naming the template placed on that tile.
+    #[serde(default)]
+    legend: HashMap<char, String>,
+    layout: String,
+}
+
+impl PrefabFile {
+    fn default_min_distance() -> f32 {
+        MIN_DISTANCE_FROM_PLAYER
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Prefab {
+    pub name: String,
+    pub file: PathBuf,
+    pub weight: i32,
+    pub levels: HashSet<usize>,
+    /// The architects this prefab may be placed into. Empty means any of them.
+    pub architects: Vec<ArchitectKind>,
+    pub min_distance_from_player: f32,
+    pub tiles: Vec<Vec<PrefabTile>>,
+}
+
+impl Prefab {
+    /// Test helper that builds a prefab straight from an ASCII layout. It isn't allowed on any
+    /// level until some are added to `levels`, and with no `architects` it fits any of them.
+    #[cfg(test)]
+    pub fn from_layout(name: &str, layout: &str, legend: &[(char, &str)]) -> Result<Self, String> {
+        let legend = legend
+            .iter()
+            .map(|&(c, template)| (c, template.to_string()))
+            .collect();
 
-pub fn apply_prefab(mb: &mut MapBuilder, prefab: &str, rng: &mut RandomNumberGenerator) {
-    let mut placement = None;
+        Ok(Self {
+            name: name.to_string(),
+            file: PathBuf::new(),
+            weight: 1,
+            levels: HashSet::new(),
+            architects: Vec::new(),
+            min_distance_from_player: MIN_DISTANCE_FROM_PLAYER,
+            tiles: parse_layout(layout, &legend, 1).map_err(|(_, message)| message)?,
+        })
+    }
+
+    pub fn width(&self) -> i32 {
+        self.tiles.first().map_or(0, |row| row.len() as i32)
+    }
+
+    pub fn height(&self) -> i32 {
+        self.tiles.len() as i32
+    }
+
+    pub fn oriented(&self, orientation: Orientation) -> Prefab {
+        let mut tiles = self.tiles.clone();
+        if orientation.mirrored {
+            tiles.iter_mut().for_each(|row| row.reverse());
+        }
+        for _ in 0..orientation.quarter_turns % 4 {
+            let height = tiles.len();
+            let width = tiles.first().map_or(0, |row| row.len());
+            tiles = (0..width)
+                .map(|x| (0..height).rev().map(|y| tiles[y][x].clone()).collect())
+                .collect();
+        }
+
+        Prefab {
+            tiles,
+            ..self.clone()
+        }
+    }
+
+    pub fn templates(&self) -> impl Iterator<Item = &str> {
+        self.tiles.iter().flatten().filter_map(|tile| match tile {
+            PrefabTile::Template(name) => Some(name.as_str()),
+            _ => None,
+        })
+    }
+
+    pub fn is_allowed(&self, level: usize, architect: ArchitectKind) -> bool {
+        self.levels.contains(&level)
+            && (self.architects.is_empty() || self.architects.contains(&architect))
+    }
+}
+
+/// Parses the rows of a layout, where the first line of `layout` is line `first_line` of its file.
+fn parse_layout(
+    layout: &str,
+    legend: &HashMap<char, String>,
+    first_line: usize,
+) -> Result<Vec<Vec<PrefabTile>>, (usize, String)> {
+    if let Some(c) = legend.keys().find(|c| PrefabTile::BUILT_IN.contains(c)) {
+        return Err((
+            first_line,
+            format!("Legend can't redefine the built in tile \"{}\"", c),
+        ));
+    }
+
+    let mut tiles: Vec<Vec<PrefabTile>> = Vec::new();
+
+    for (line, text) in (first_line..).zip(layout.lines()) {
+        let text = text.trim();
+        if text.is_empty() {
+            continue;
+        }
+
+        let row = text
+            .chars()
+            .map(|c| {
+                PrefabTile::parse(c, legend)
+                    .ok_or_else(|| (line, format!("Unsupported Prefab Tile \"{}\"", c)))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if let Some(first_row) = tiles.first() {
+            if first_row.len() != row.len() {
+                return Err((
+                    line,
+                    format!(
+                        "Row is {} tiles wide, but the first row is {}",
+                        row.len(),
+                        first_row.len()
+                    ),
+                ));
+            }
+        }
+        tiles.push(row);
+    }
+
+    if tiles.is_empty() {
+        return Err((first_line, "Layout is empty".to_string()));
+    }
+
+    Ok(tiles)
+}
+
+/// The line of `text` that the `layout` string starts on.
+fn layout_line(text: &str) -> usize {
+    text.match_indices("layout")
+        .find_map(|(start, key)| {
+            let rest = text[start + key.len()..].trim_start().strip_prefix(':')?;
+            let quote = rest.find('"')?;
+            let offset = text.len() - rest.len() + quote;
+            Some(text[..offset].matches('\n').count() + 1)
+        })
+        .unwrap_or(1)
+}
+
+#[derive(Debug)]
+pub struct PrefabError {
+    pub file: PathBuf,
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for PrefabError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.file.display(), line, self.message),
+            None => write!(f, "{}: {}", self.file.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for PrefabError {}
+
+#[derive(Clone, Debug, Default)]
+pub struct Prefabs {
+    pub prefabs: Vec<Prefab>,
+}
+
+impl Prefabs {
+    pub fn load() -> Result<Self, PrefabError> {
+        Self::load_dir(&resource_dir().join(PREFAB_DIR))
+    }
+
+    /// Loads every `.ron` file in `dir`, sorted by name so seeded runs pick the same prefabs.
+    pub fn load_dir(dir: &Path) -> Result<Self, PrefabError> {
+        let error = |file: &Path, line, message: String| PrefabError {
+            file: file.to_path_buf(),
+            line,
+            message,
+        };
+
+        let mut files = fs::read_dir(dir)
+            .map_err(|err| error(dir, None, err.to_string()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.extension().is_some_and(|ext| ext == "ron"))
+            .collect::<Vec<_>>();
+        files.sort();
+
+        let mut prefabs = Vec::new();
+        for file in files {
+            let text =
+                fs::read_to_string(&file).map_err(|err| error(&file, None, err.to_string()))?;
+            let data: PrefabFile = ron::from_str(&text)
+                .map_err(|err| error(&file, Some(err.position.line), err.code.to_string()))?;
+            let tiles = parse_layout(&data.layout, &data.legend, layout_line(&text))
+                .map_err(|(line, message)| error(&file, Some(line), message))?;
+
+            prefabs.push(Prefab {
+                name: file
+                    .file_stem()
+                    .map(|stem| stem.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                file,
+                weight: data.weight,
+                levels: data.levels,
+                architects: data.architects,
+                min_distance_from_player: data.min_distance_from_player,
+                tiles,
+            });
+        }
+
+        Ok(Self { prefabs })
+    }
+
+    /// Checks every legend names a template that exists, so a typo fails at load time rather
+    /// than when the vault is first placed.
+    pub fn check_templates(&self, templates: &Templates) -> Result<(), PrefabError> {
+        for prefab in &self.prefabs {
+            if let Some(name) = prefab
+                .templates()
+                .find(|&name| templates.find(name).is_none())
+            {
+                return Err(PrefabError {
+                    file: prefab.file.clone(),
+                    line: None,
+                    message: format!("Legend names the unknown template \"{}\"", name),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Picks up to `MAX_PREFABS_PER_LEVEL` prefabs by weight, from those allowed on this level.
+    pub fn choose(
+        &self,
+        level: usize,
+        architect: ArchitectKind,
+        rng: &mut RandomNumberGenerator,
+    ) -> Vec<&Prefab> {
+        let candidates: Vec<&Prefab> = self
+            .prefabs
+            .iter()
+            .filter(|prefab| prefab.weight > 0 && prefab.is_allowed(level, architect))
+            .collect();
+        let total_weight: i32 = candidates.iter().map(|prefab| prefab.weight).sum();
+        if total_weight == 0 {
+            return Vec::new();
+        }
 
+        (0..rng.range(0, MAX_PREFABS_PER_LEVEL + 1))
+            .filter_map(|_| {
+                let mut roll = rng.range(0, total_weight);
+                candidates.iter().copied().find(|prefab| {
+                    roll -= prefab.weight;
+                    roll < 0
+                })
+            })
+            .collect()
+    }
+}
+
+/// Why `apply_prefab` couldn't find anywhere to put a prefab.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PlacementError {
+    TooBig {
+        prefab: String,
+    },
+    NoValidLocation {
+        prefab: String,
+        /// Attempts that landed on the player start or the amulet.
+        blocked: i32,
+        /// Attempts that overlapped a prefab placed earlier.
+        overlapping: i32,
+        /// Attempts with nowhere reachable at least `min_distance_from_player` away.
+        too_close: i32,
+    },
+}
+
+impl fmt::Display for PlacementError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PlacementError::TooBig { prefab } => {
+                write!(f, "Prefab \"{}\" is bigger than the map", prefab)
+            }
+            PlacementError::NoValidLocation {
+                prefab,
+                blocked,
+                overlapping,
+                too_close,
+            } => write!(
+                f,
+                "No room for prefab \"{}\" after {} attempts: {} covered the player or amulet, \
+                 {} overlapped another prefab, {} were too close to the player",
+                prefab, MAX_ATTEMPTS, blocked, overlapping, too_close
+            ),
+        }
+    }
+}
+
+impl std::error::Error for PlacementError {}
+
+/// Places `prefab` somewhere random in a random orientation, away from the player and clear of
+/// the prefabs already in `mb.vaults`, returning the area it now covers.
+pub fn apply_prefab(
+    mb: &mut MapBuilder,
+    prefab: &Prefab,
+    rng: &mut RandomNumberGenerator,
+) -> Result<Rect, PlacementError> {
     let flow_map = DijkstraMap::new(
-        SCREEN_WIDTH,
-        SCREEN_HEIGHT,
+        mb.map.width,
+        mb.map.height,
         &[mb.map.point2d_to_index(mb.player_start)],
         &mb.map,
         MAX_FLOWMAP_DISTANCE,
     );
 
-    let (width, height) = prefab_size(prefab);
+    let (mut blocked, mut overlapping, mut too_close) = (0, 0, 0);
+    let mut fits_somehow = false;
+
+    for _ in 0..MAX_ATTEMPTS {
+        let oriented = prefab.oriented(Orientation::random(rng));
+        let (width, height) = (oriented.width(), oriented.height());
+        if width > mb.map.width || height > mb.map.height {
+            continue;
+        }
+        fits_somehow = true;
 
-    let mut attempts = 0;
-    while placement.is_none() && attempts < MAX_ATTEMPTS {
         let dimensions = Rect::with_size(
-            rng.range(0, SCREEN_WIDTH - width),
-            rng.range(0, SCREEN_HEIGHT - height),
+            rng.range(0, mb.map.width - width + 1),
+            rng.range(0, mb.map.height - height + 1),
             width,
             height,
         );
 
-        let mut can_place = false;
+        let covers = |pt: Point| {
+            pt.x >= dimensions.x1
+                && pt.x < dimensions.x2
+                && pt.y >= dimensions.y1
+                && pt.y < dimensions.y2
+        };
+        if covers(mb.player_start) || covers(mb.amulet_start) {
+            blocked += 1;
+            continue;
+        }
+
+        if mb.vaults.iter().any(|vault| vault.intersect(&dimensions)) {
+            overlapping += 1;
+            continue;
+        }
+
+        let mut far_enough = false;
         dimensions.for_each(|pt| {
-            let idx = mb.map.point2d_to_index(pt);
-            let distance = flow_map.map[idx];
-            if distance < MAX_FLOWMAP_DISTANCE
-                && distance > MIN_DISTANCE_FROM_PLAYER
-                && mb.amulet_start != pt
-            {
-                can_place = true;
+            let distance = flow_map.map[mb.map.point2d_to_index(pt)];
+            if distance < MAX_FLOWMAP_DISTANCE && distance > prefab.min_distance_from_player {
+                far_enough = true;
             }
         });
-
-        if can_place {
-            placement = Some(Point::new(dimensions.x1, dimensions.y1));
-            let points = dimensions.point_set();
-            mb.monster_spawns.retain(|pt| !points.contains(pt))
+        if !far_enough {
+            too_close += 1;
+            continue;
         }
 
-        attempts += 1;
+        place_prefab(Point::new(dimensions.x1, dimensions.y1), mb, &oriented);
+        mb.vaults.push(dimensions);
+        return Ok(dimensions);
     }
 
-    if let Some(placement) = placement {
-        place_prefab(placement, mb, prefab);
+    if fits_somehow {
+        Err(PlacementError::NoValidLocation {
+            prefab: prefab.name.clone(),
+            blocked,
+            overlapping,
+            too_close,
+        })
+    } else {
+        Err(PlacementError::TooBig {
+            prefab: prefab.name.clone(),
+        })
     }
 }
 
-fn prefab_size(prefab: &str) -> (i32, i32) {
-    let filled_lines: Vec<&str> = prefab
-        .lines()
-        .filter(|line| !line.trim().is_empty())
-        .collect();
+pub fn place_prefab(placement: Point, mb: &mut MapBuilder, prefab: &Prefab) {
+    for (row, ty) in prefab.tiles.iter().zip(0..) {
+        for (tile, tx) in row.iter().zip(0..) {
+            let pt = Point::new(tx + placement.x, ty + placement.y);
+            let idx = mb.map.point2d_to_index(pt);
 
-    let height: i32 = filled_lines
-        .len()
-        .try_into()
-        .expect("error calculating height");
-    let mut width: i32 = 0;
-    if let Some(first_line) = filled_lines.first() {
-        width = first_line
-            .trim()
-            .chars()
-            .count()
-            .try_into()
-            .expect("error calculating width");
-    }
-
-    (width, height)
-}
-
-pub fn place_prefab(placement: Point, mb: &mut MapBuilder, prefab: &str) {
-    for (line, ty) in prefab.lines().zip(0..) {
-        let line = line.trim();
-        for (c, tx) in line.chars().zip(0..) {
-            let idx = map_idx(tx + placement.x, ty + placement.y);
-            match c {
-                'M' => {
-                    mb.map.tiles[idx] = TileType::Floor;
-                    mb.monster_spawns.push(Point::new(tx, ty));
-                }
-                '-' => mb.map.tiles[idx] = TileType::Floor,
-                '#' => mb.map.tiles[idx] = TileType::Wall,
-                '@' => mb.player_start = Point::new(tx, ty),
-                _ => panic!("Unsupported Prefab Tile \"{}\"", c),
+            if *tile != PrefabTile::Floor {
+                mb.monster_spawns.retain(|&spawn| spawn != pt);
+            }
+
+            mb.map.tiles[idx] = match tile {
+                PrefabTile::Wall => TileType::Wall,
+                _ => TileType::Floor,
+            };
+
+            match tile {
+                PrefabTile::Monster => mb.monster_spawns.push(pt),
+                PrefabTile::Item => mb.item_spawns.push(pt),
+                PrefabTile::Template(name) => mb.template_spawns.push((pt, name.clone())),
+                PrefabTile::PlayerStart => mb.player_start = pt,
+                PrefabTile::Exit => mb.amulet_start = pt,
+                PrefabTile::Floor | PrefabTile::NoSpawn | PrefabTile::Wall => {}
             }
         }
     }
@@ -122,36 +499,282 @@ mod test {
             .count()
     }
 
+    fn fortress() -> Prefab {
+        Prefabs::load()
+            .unwrap()
+            .prefabs
+            .into_iter()
+            .find(|prefab| prefab.name == "fortress")
+            .expect("Expected the fortress prefab to ship")
+    }
+
     #[test]
     fn test_place_fortress() {
         let mut rng = RandomNumberGenerator::new();
 
-        let mut mb = EmptyArchitect {}.build(&mut rng);
+        let mut mb = EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
 
-        apply_prefab(&mut mb, FORTRESS, &mut rng);
+        apply_prefab(&mut mb, &fortress(), &mut rng).unwrap();
 
         assert_eq!(count_walls(mb.map), 32)
     }
 
     #[test]
     fn test_place_square() {
-        let square = "
+        let square = Prefab::from_layout(
+            "square",
+            "
         ##
         ##
-        ";
+        ",
+            &[],
+        )
+        .unwrap();
         let mut rng = RandomNumberGenerator::new();
 
-        let mut mb = EmptyArchitect {}.build(&mut rng);
+        let mut mb = EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
 
-        apply_prefab(&mut mb, square, &mut rng);
+        apply_prefab(&mut mb, &square, &mut rng).unwrap();
 
         assert_eq!(count_walls(mb.map), 4)
     }
 
     #[test]
     fn test_fortress_size() {
-        let (width, height) = prefab_size(FORTRESS);
-        assert_eq!(width, 12);
-        assert_eq!(height, 11);
+        let fortress = fortress();
+        assert_eq!(fortress.width(), 12);
+        assert_eq!(fortress.height(), 11);
+    }
+
+    #[test]
+    fn test_error_names_file_and_line() {
+        let dir = std::env::temp_dir().join(format!("prefab_test_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("broken.ron"),
+            "Prefab (\n    weight: 1,\n    levels: [0],\n    layout: \"\n---\n-X-\n---\n\",\n)\n",
+        )
+        .unwrap();
+
+        let err = Prefabs::load_dir(&dir).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(err.file, dir.join("broken.ron"));
+        assert_eq!(err.line, Some(6));
+        assert!(err
+            .to_string()
+            .ends_with("broken.ron:6: Unsupported Prefab Tile \"X\""));
+    }
+
+    #[test]
+    fn test_choose_respects_levels_and_architects() {
+        let mut caves_only = Prefab::from_layout("caves", "--", &[]).unwrap();
+        caves_only.levels.insert(1);
+        caves_only.architects.push(ArchitectKind::CellularAutomata);
+        let prefabs = Prefabs {
+            prefabs: vec![caves_only],
+        };
+        let mut rng = RandomNumberGenerator::seeded(1);
+
+        for _ in 0..20 {
+            assert!(prefabs
+                .choose(0, ArchitectKind::CellularAutomata, &mut rng)
+                .is_empty());
+            assert!(prefabs.choose(1, ArchitectKind::Rooms, &mut rng).is_empty());
+        }
+        assert!((0..20).any(|_| !prefabs
+            .choose(1, ArchitectKind::CellularAutomata, &mut rng)
+            .is_empty()));
+    }
+
+    #[test]
+    fn test_legend_places_in_map_coordinates() {
+        let guard_post = Prefab::from_layout(
+            "guard_post",
+            "
+            #####
+            #O$>#
+            #M.@#
+            #####
+            ",
+            &[('O', "Ogre")],
+        )
+        .unwrap();
+        let mut rng = RandomNumberGenerator::seeded(1);
+        let mut mb = EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
+        mb.monster_spawns = vec![Point::new(12, 7), Point::new(40, 40)];
+
+        place_prefab(Point::new(10, 5), &mut mb, &guard_post);
+
+        assert_eq!(
+            mb.template_spawns,
+            vec![(Point::new(11, 6), "Ogre".to_string())]
+        );
+        assert_eq!(mb.item_spawns, vec![Point::new(12, 6)]);
+        assert_eq!(mb.amulet_start, Point::new(13, 6));
+        assert_eq!(mb.player_start, Point::new(13, 7));
+        assert_eq!(
+            mb.monster_spawns,
+            vec![Point::new(40, 40), Point::new(11, 7)]
+        );
+    }
+
+    #[test]
+    fn test_unknown_template_is_rejected() {
+        let prefabs = Prefabs {
+            prefabs: vec![Prefab::from_layout("typo", "-X-", &[('X', "Ogrre")]).unwrap()],
+        };
+
+        let err = prefabs
+            .check_templates(&Templates::load().unwrap())
+            .unwrap_err();
+
+        assert!(err.message.contains("Ogrre"));
+    }
+
+    #[test]
+    fn test_legend_cannot_redefine_built_in_tiles() {
+        assert!(Prefab::from_layout("walls", "#", &[('#', "Ogre")]).is_err());
+    }
+
+    #[test]
+    fn test_orientations() {
+        let corner = Prefab::from_layout(
+            "corner",
+            "
+            #--
+            ##-
+            ",
+            &[],
+        )
+        .unwrap();
+        let turned = |quarter_turns, mirrored| {
+            corner.oriented(Orientation {
+                quarter_turns,
+                mirrored,
+            })
+        };
+
+        let expected = Prefab::from_layout(
+            "corner",
+            "
+            ##
+            #-
+            --
+            ",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(turned(1, false).tiles, expected.tiles);
+
+        let expected = Prefab::from_layout(
+            "corner",
+            "
+            --#
+            -##
+            ",
+            &[],
+        )
+        .unwrap();
+        assert_eq!(turned(0, true).tiles, expected.tiles);
+
+        assert_eq!(turned(4, false), corner);
+        let unturned = turned(1, true)
+            .oriented(Orientation {
+                quarter_turns: 3,
+                mirrored: false,
+            })
+            .oriented(Orientation {
+                quarter_turns: 0,
+                mirrored: true,
+            });
+        assert_eq!(unturned, corner);
+    }
+
+    #[test]
+    fn test_placements_do_not_overlap() {
+        let mut rng = RandomNumberGenerator::seeded(7);
+        let mut mb = EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
+        let mut fortress = fortress();
+        fortress.min_distance_from_player = 0.0;
+
+        let placed: Vec<Rect> = (0..4)
+            .filter_map(|_| apply_prefab(&mut mb, &fortress, &mut rng).ok())
+            .collect();
+
+        assert!(placed.len() > 1);
+        assert_eq!(mb.vaults, placed);
+        for (i, a) in placed.iter().enumerate() {
+            assert!(placed[i + 1..].iter().all(|b| !a.intersect(b)));
+        }
+    }
+
+    #[test]
+    fn test_build_reports_prefabs_it_could_not_place() {
+        let mut huge = Prefab::from_layout("huge", &"-".repeat(31), &[]).unwrap();
+        huge.levels.insert(0);
+        let prefabs = Prefabs {
+            prefabs: vec![huge],
+        };
+
+        let errors: Vec<PlacementError> = (0..10)
+            .flat_map(|seed| {
+                let mut rng = RandomNumberGenerator::seeded(seed);
+                MapBuilder::new(30, 20)
+                    .build(0, &prefabs, &mut rng)
+                    .placement_errors
+            })
+            .collect();
+
+        assert!(!errors.is_empty());
+        assert!(errors.iter().all(|err| *err
+            == PlacementError::TooBig {
+                prefab: "huge".to_string()
+            }));
+    }
+
+    #[test]
+    fn test_prefab_the_size_of_the_map() {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        let mut mb = EmptyArchitect {}.build(12, 8, &mut rng);
+
+        // It can't help covering the player, but trying mustn't crash.
+        let whole_map =
+            Prefab::from_layout("whole_map", &vec!["-".repeat(12); 8].join("\n"), &[]).unwrap();
+        assert!(matches!(
+            apply_prefab(&mut mb, &whole_map, &mut rng),
+            Err(PlacementError::NoValidLocation { .. })
+        ));
+
+        let mut strip = Prefab::from_layout("strip", &"-".repeat(12), &[]).unwrap();
+        strip.min_distance_from_player = 0.0;
+        let placed = apply_prefab(&mut mb, &strip, &mut rng).unwrap();
+        assert_eq!((placed.x1, placed.x2), (0, 12));
+    }
+
+    #[test]
+    fn test_placement_failure_reports_why() {
+        let mut rng = RandomNumberGenerator::seeded(1);
+        let mut mb = EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
+        let mut far_away = Prefab::from_layout("far_away", "#", &[]).unwrap();
+        far_away.min_distance_from_player = 1000.0;
+
+        match apply_prefab(&mut mb, &far_away, &mut rng) {
+            Err(PlacementError::NoValidLocation {
+                blocked,
+                overlapping,
+                too_close,
+                ..
+            }) => assert_eq!(blocked + overlapping + too_close, MAX_ATTEMPTS),
+            other => panic!("Expected no valid location, got {:?}", other),
+        }
+
+        let huge = Prefab::from_layout("huge", &"-".repeat(MAP_WIDTH as usize + 1), &[]).unwrap();
+        assert_eq!(
+            apply_prefab(&mut mb, &huge, &mut rng),
+            Err(PlacementError::TooBig {
+                prefab: "huge".to_string()
+            })
+        );
     }
 }
@@ -5,12 +5,13 @@ use super::MapArchitect;
 pub struct RoomsArchitect {}
 
 impl MapArchitect for RoomsArchitect {
-    fn build(&mut self, rng: &mut RandomNumberGenerator) -> MapBuilder {
-        let mut mb = MapBuilder::new();
+    fn build(&mut self, width: i32, height: i32, rng: &mut RandomNumberGenerator) -> MapBuilder {
+        let mut mb = MapBuilder::new(width, height);
 
         mb.fill(TileType::Wall);
         mb.build_random_rooms(rng);
         mb.build_corridors(rng);
+        mb.build_doors();
         mb.player_start = mb.rooms[0].center();
 
         mb.amulet_start = mb.find_most_distant();
@@ -1,13 +1,37 @@
 use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Identifies a `MapTheme` so the active theme can be saved and restored.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ThemeId {
+    Dungeon,
+    Forest,
+}
+
+impl ThemeId {
+    pub fn theme(self) -> Box<dyn MapTheme> {
+        match self {
+            ThemeId::Dungeon => Box::new(DungeonTheme {}),
+            ThemeId::Forest => Box::new(ForestTheme {}),
+        }
+    }
+}
 
 pub struct DungeonTheme {}
 
 impl MapTheme for DungeonTheme {
+    fn id(&self) -> ThemeId {
+        ThemeId::Dungeon
+    }
+
     fn tile_to_render(&self, tile_type: TileType) -> FontCharType {
         match tile_type {
             TileType::Wall => to_cp437('#'),
             TileType::Floor => to_cp437('.'),
             TileType::Exit => to_cp437('>'),
+            TileType::DoorClosed => to_cp437('+'),
+            TileType::DoorOpen => to_cp437('\''),
+            TileType::UpStairs => to_cp437('<'),
         }
     }
 }
@@ -15,11 +39,18 @@ impl MapTheme for DungeonTheme {
 pub struct ForestTheme {}
 
 impl MapTheme for ForestTheme {
+    fn id(&self) -> ThemeId {
+        ThemeId::Forest
+    }
+
     fn tile_to_render(&self, tile_type: TileType) -> FontCharType {
         match tile_type {
             TileType::Wall => to_cp437('"'),
             TileType::Floor => to_cp437(';'),
             TileType::Exit => to_cp437('>'),
+            TileType::DoorClosed => to_cp437('='),
+            TileType::DoorOpen => to_cp437('_'),
+            TileType::UpStairs => to_cp437('<'),
         }
     }
 }
@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+const MAX_MESSAGES: usize = 200;
+
+/// What a message is about, which decides the color it's drawn in.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MessageCategory {
+    /// Something the player did to a monster.
+    Combat,
+    /// Something that hurt the player.
+    Danger,
+    Item,
+    /// Changes to the dungeon itself, like taking the stairs.
+    Event,
+    Info,
+}
+
+impl MessageCategory {
+    pub fn color(self) -> (u8, u8, u8) {
+        match self {
+            MessageCategory::Combat => ORANGE,
+            MessageCategory::Danger => RED,
+            MessageCategory::Item => GREEN,
+            MessageCategory::Event => YELLOW,
+            MessageCategory::Info => LIGHT_GRAY,
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    pub category: MessageCategory,
+}
+
+/// Everything the player has been told this run, newest last.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct MessageLog {
+    messages: Vec<Message>,
+    /// How many lines back from the newest message the HUD is scrolled.
+    scroll: usize,
+}
+
+impl MessageLog {
+    pub fn add(&mut self, category: MessageCategory, text: impl Into<String>) {
+        self.messages.push(Message {
+            text: text.into(),
+            category,
+        });
+        if self.messages.len() > MAX_MESSAGES {
+            self.messages.remove(0);
+        }
+        self.scroll = 0;
+    }
+
+    /// Scrolls back through older messages for positive `lines`, and forward for negative ones.
+    pub fn scroll(&mut self, lines: i32) {
+        let scroll = self.scroll as i32 + lines;
+        self.scroll = scroll.clamp(0, self.messages.len().saturating_sub(1) as i32) as usize;
+    }
+
+    pub fn is_scrolled(&self) -> bool {
+        self.scroll > 0
+    }
+
+    /// The `lines` messages that fit in the scrollback panel at its current position, oldest first.
+    pub fn visible(&self, lines: usize) -> &[Message] {
+        let end = self.messages.len() - self.scroll;
+        &self.messages[end.saturating_sub(lines)..end]
+    }
+}
+
+/// How an entity is referred to in a message: "you" for the player, "the Orc" for anything named.
+pub fn display_name(ecs: &SubWorld, entity: Entity) -> String {
+    let entry = match ecs.entry_ref(entity) {
+        Ok(entry) => entry,
+        Err(_) => return "something".to_string(),
+    };
+
+    if entry.get_component::<Player>().is_ok() {
+        "you".to_string()
+    } else if let Ok(name) = entry.get_component::<Name>() {
+        format!("the {}", name.0)
+    } else {
+        "something".to_string()
+    }
+}
+
+/// Starts a sentence with `subject` doing `verb`, e.g. "You hit" or "The Orc hits". Only the
+/// first word of the verb is conjugated, so "take off" becomes "takes off".
+pub fn subject_verb(subject: &str, verb: &str) -> String {
+    if subject == "you" {
+        return format!("You {}", verb);
+    }
+
+    let (first, rest) = match verb.split_once(' ') {
+        Some((first, rest)) => (first, format!(" {}", rest)),
+        None => (verb, String::new()),
+    };
+    let ending = if ["s", "sh", "ch", "x"]
+        .iter()
+        .any(|end| first.ends_with(end))
+    {
+        "es"
+    } else {
+        "s"
+    };
+    format!("{} {}{}{}", capitalize(subject), first, ending, rest)
+}
+
+pub fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn log_with(count: usize) -> MessageLog {
+        let mut log = MessageLog::default();
+        for i in 0..count {
+            log.add(MessageCategory::Info, format!("Message {}", i));
+        }
+        log
+    }
+
+    fn texts(messages: &[Message]) -> Vec<&str> {
+        messages
+            .iter()
+            .map(|message| message.text.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_visible_shows_newest() {
+        let log = log_with(5);
+
+        assert_eq!(texts(log.visible(2)), vec!["Message 3", "Message 4"]);
+        assert_eq!(log.visible(10).len(), 5);
+    }
+
+    #[test]
+    fn test_scrolling() {
+        let mut log = log_with(5);
+
+        log.scroll(2);
+        assert_eq!(texts(log.visible(2)), vec!["Message 1", "Message 2"]);
+
+        log.scroll(10);
+        assert_eq!(texts(log.visible(2)), vec!["Message 0"]);
+
+        log.scroll(-10);
+        assert!(!log.is_scrolled());
+
+        log.scroll(3);
+        log.add(MessageCategory::Danger, "Ouch");
+        assert!(!log.is_scrolled());
+        assert_eq!(log.visible(1)[0].category, MessageCategory::Danger);
+    }
+
+    #[test]
+    fn test_subject_verb() {
+        assert_eq!(subject_verb("you", "miss"), "You miss");
+        assert_eq!(subject_verb("the Orc", "miss"), "The Orc misses");
+        assert_eq!(subject_verb("the Orc", "take off"), "The Orc takes off");
+    }
+
+    #[test]
+    fn test_oldest_messages_are_dropped() {
+        let log = log_with(MAX_MESSAGES + 3);
+
+        let all = log.visible(MAX_MESSAGES * 2);
+        assert_eq!(all.len(), MAX_MESSAGES);
+        assert_eq!(all[0].text, "Message 3");
+    }
+}
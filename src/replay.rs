@@ -0,0 +1,292 @@
+use std::{error::Error, fs};
+
+use ron::ser::PrettyConfig;
+use serde::{Deserialize, Serialize};
+
+use crate::prelude::*;
+
+pub const REPLAY_FILE: &str = "replay.ron";
+const PLAYBACK_SPEEDS: [f32; 7] = [0.125, 0.25, 0.5, 1.0, 2.0, 4.0, 8.0];
+const NORMAL_SPEED: usize = 3;
+
+/// The input `Game::tick` was given for one or more consecutive frames.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ReplayFrame {
+    #[serde(with = "key_code_serde", default)]
+    pub key: Option<VirtualKeyCode>,
+    pub mouse: Point,
+    #[serde(default = "ReplayFrame::one")]
+    pub repeat: u32,
+}
+
+impl ReplayFrame {
+    fn one() -> u32 {
+        1
+    }
+}
+
+/// Everything needed to play a run back exactly: its seed, the save it was resumed from if it
+/// didn't start fresh, and the input of every frame.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: RunSeed,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resumed_from: Option<String>,
+    pub frames: Vec<ReplayFrame>,
+}
+
+impl Replay {
+    pub fn new(seed: RunSeed) -> Self {
+        Self {
+            seed,
+            resumed_from: None,
+            frames: Vec::new(),
+        }
+    }
+
+    /// Records a run picking up from `save`, the text of the save game it was loaded from.
+    pub fn resumed(seed: RunSeed, save: String) -> Self {
+        Self {
+            resumed_from: Some(save),
+            ..Self::new(seed)
+        }
+    }
+
+    /// Appends a frame, folding idle frames with an unchanged mouse into the previous one.
+    pub fn record(&mut self, key: Option<VirtualKeyCode>, mouse: Point) {
+        if let Some(last) = self.frames.last_mut() {
+            if key.is_none() && last.key.is_none() && last.mouse == mouse {
+                last.repeat += 1;
+                return;
+            }
+        }
+
+        self.frames.push(ReplayFrame {
+            key,
+            mouse,
+            repeat: 1,
+        });
+    }
+
+    pub fn ticks(&self) -> impl Iterator<Item = (Option<VirtualKeyCode>, Point)> + '_ {
+        self.frames
+            .iter()
+            .flat_map(|frame| (0..frame.repeat).map(move |_| (frame.key, frame.mouse)))
+    }
+
+    pub fn len(&self) -> usize {
+        self.frames.iter().map(|frame| frame.repeat as usize).sum()
+    }
+
+    pub fn save(&self, path: &str) -> Result<(), Box<dyn Error>> {
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(self, PrettyConfig::default())?,
+        )?;
+        Ok(())
+    }
+
+    pub fn load(path: &str) -> Result<Self, Box<dyn Error>> {
+        Ok(ron::from_str(&fs::read_to_string(path)?)?)
+    }
+
+    /// Puts `game` where the recording began: a fresh start from the seed, or the save it was
+    /// resumed from.
+    pub fn start_game(&self, game: &mut Game) -> Result<(), Box<dyn Error>> {
+        match &self.resumed_from {
+            Some(save) => {
+                let (ecs, resources) = deserialize_game(save)?;
+                game.load(ecs, resources);
+            }
+            None => game.start(self.seed),
+        }
+        Ok(())
+    }
+
+    /// Starts a game the way the recording did and feeds it every recorded frame.
+    pub fn play(&self) -> Result<Game, Box<dyn Error>> {
+        let mut game = Game::new()?;
+        self.start_game(&mut game)?;
+        for (key, mouse) in self.ticks() {
+            game.tick(key, mouse);
+        }
+        Ok(game)
+    }
+}
+
+/// Feeds a `Replay` into a `Game` a few frames at a time, with pause and speed controls.
+pub struct Playback {
+    ticks: Vec<(Option<VirtualKeyCode>, Point)>,
+    position: usize,
+    speed: usize,
+    pending: f32,
+    pub paused: bool,
+}
+
+impl Playback {
+    pub fn new(replay: &Replay) -> Self {
+        Self {
+            ticks: replay.ticks().collect(),
+            position: 0,
+            speed: NORMAL_SPEED,
+            pending: 0.0,
+            paused: false,
+        }
+    }
+
+    pub fn speed(&self) -> f32 {
+        PLAYBACK_SPEEDS[self.speed]
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.position >= self.ticks.len()
+    }
+
+    pub fn progress(&self) -> (usize, usize) {
+        (self.position, self.ticks.len())
+    }
+
+    /// Space pauses, `+`/`-` change the speed and `.` steps a single frame while paused.
+    pub fn handle_key(&mut self, key: Option<VirtualKeyCode>) -> bool {
+        match key {
+            Some(VirtualKeyCode::Space) => self.paused = !self.paused,
+            Some(VirtualKeyCode::Equals | VirtualKeyCode::Plus | VirtualKeyCode::NumpadAdd) => {
+                self.speed = usize::min(self.speed + 1, PLAYBACK_SPEEDS.len() - 1)
+            }
+            Some(VirtualKeyCode::Minus | VirtualKeyCode::NumpadSubtract) => {
+                self.speed = self.speed.saturating_sub(1)
+            }
+            Some(VirtualKeyCode::Period) if self.paused => return true,
+            _ => {}
+        }
+        false
+    }
+
+    /// Advances the playback by one rendered frame, ticking `game` as often as the speed allows.
+    pub fn advance(&mut self, game: &mut Game, key: Option<VirtualKeyCode>) {
+        let step = self.handle_key(key);
+
+        let mut ticks = if self.paused {
+            usize::from(step)
+        } else {
+            self.pending += self.speed();
+            let ticks = self.pending.floor();
+            self.pending -= ticks;
+            ticks as usize
+        };
+
+        while ticks > 0 && !self.is_finished() {
+            let (key, mouse) = self.ticks[self.position];
+            game.tick(key, mouse);
+            self.position += 1;
+            ticks -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn record_run(seed: RunSeed, keys: &[VirtualKeyCode]) -> (Replay, Game) {
+        let mut replay = Replay::new(seed);
+        let mut game = Game::new().unwrap();
+        game.start(seed);
+        record_keys(&mut replay, &mut game, keys);
+
+        (replay, game)
+    }
+
+    fn record_keys(replay: &mut Replay, game: &mut Game, keys: &[VirtualKeyCode]) {
+        let mut keys = keys.iter();
+
+        while game.is_in_progress() {
+            let key = if game.turn_state() == TurnState::AwaitingInput {
+                match keys.next() {
+                    Some(key) => Some(*key),
+                    None => break,
+                }
+            } else {
+                None
+            };
+            replay.record(key, Point::zero());
+            game.tick(key, Point::zero());
+        }
+    }
+
+    fn player_pos(game: &Game) -> Point {
+        *<&Point>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .next()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_idle_frames_are_folded() {
+        let mut replay = Replay::new(RunSeed(1));
+        replay.record(None, Point::zero());
+        replay.record(None, Point::zero());
+        replay.record(Some(VirtualKeyCode::Up), Point::zero());
+        replay.record(None, Point::new(1, 1));
+
+        assert_eq!(replay.frames.len(), 3);
+        assert_eq!(replay.len(), 4);
+    }
+
+    #[test]
+    fn test_replay_reproduces_run() {
+        let keys = parse_key_codes("Up,Up,Right,Right,Down,G,Left,Left,Down,Up").unwrap();
+        let (replay, game) = record_run(RunSeed(99), &keys);
+
+        let text = ron::to_string(&replay).unwrap();
+        let replay: Replay = ron::from_str(&text).unwrap();
+        let replayed = replay.play().unwrap();
+
+        assert_eq!(replayed.turn_state(), game.turn_state());
+        assert_eq!(player_pos(&replayed), player_pos(&game));
+        assert_eq!(replayed.ecs.len(), game.ecs.len());
+    }
+
+    #[test]
+    fn test_resumed_replay_reproduces_run() {
+        let keys = parse_key_codes("Up,Up,Right,Right,G").unwrap();
+        let (_, before_save) = record_run(RunSeed(7), &keys);
+        let save = serialize_game(&before_save.ecs, &before_save.resources).unwrap();
+
+        let (ecs, resources) = deserialize_game(&save).unwrap();
+        let mut game = Game::new().unwrap();
+        game.load(ecs, resources);
+        let mut replay = Replay::resumed(RunSeed(7), save);
+        let keys = parse_key_codes("Down,Down,Left,Up,Left").unwrap();
+        record_keys(&mut replay, &mut game, &keys);
+
+        let text = ron::to_string(&replay).unwrap();
+        let replay: Replay = ron::from_str(&text).unwrap();
+        let replayed = replay.play().unwrap();
+
+        assert_eq!(replayed.turn_state(), game.turn_state());
+        assert_eq!(player_pos(&replayed), player_pos(&game));
+        assert_eq!(replayed.ecs.len(), game.ecs.len());
+    }
+
+    #[test]
+    fn test_playback_pause_and_speed() {
+        let keys = parse_key_codes("Up,Down").unwrap();
+        let (replay, _) = record_run(RunSeed(3), &keys);
+        let mut game = Game::new().unwrap();
+        game.start(replay.seed);
+        let mut playback = Playback::new(&replay);
+
+        playback.advance(&mut game, Some(VirtualKeyCode::Space));
+        assert_eq!(playback.progress().0, 0);
+
+        playback.advance(&mut game, Some(VirtualKeyCode::Period));
+        assert_eq!(playback.progress().0, 1);
+
+        playback.advance(&mut game, Some(VirtualKeyCode::Space));
+        playback.advance(&mut game, Some(VirtualKeyCode::Equals));
+        assert_eq!(playback.speed(), 2.0);
+        assert_eq!(playback.progress().0, 4);
+    }
+}
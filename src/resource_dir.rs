@@ -0,0 +1,19 @@
+use std::path::{Path, PathBuf};
+
+const RESOURCE_DIR: &str = "resources";
+
+/// Where the game's data files live, found from the executable rather than the working directory.
+/// It's looked for beside the executable and then in each directory above it, which finds the
+/// crate's own `resources` from `target/debug` under `cargo run` and `cargo test` alike. Falls back
+/// to the working directory if there's none.
+pub fn resource_dir() -> PathBuf {
+    std::env::current_exe()
+        .ok()
+        .and_then(|exe| {
+            exe.ancestors()
+                .skip(1)
+                .map(|dir| dir.join(RESOURCE_DIR))
+                .find(|dir| dir.is_dir())
+        })
+        .unwrap_or_else(|| Path::new(RESOURCE_DIR).to_path_buf())
+}
@@ -0,0 +1,51 @@
+use crate::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The seed every random decision in a run is derived from.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct RunSeed(pub u64);
+
+impl RunSeed {
+    pub fn random() -> Self {
+        Self(RandomNumberGenerator::new().rand())
+    }
+
+    /// The shared `RandomNumberGenerator` is reseeded from this whenever a level is built, so a
+    /// seed and a depth are all it takes to rebuild the same dungeon.
+    pub fn level_rng(&self, level: usize) -> RandomNumberGenerator {
+        RandomNumberGenerator::seeded(self.0 ^ (level as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_same_seed_same_dungeon() {
+        let seed = RunSeed(1234);
+        let prefabs = Prefabs::load().unwrap();
+
+        let first =
+            MapBuilder::new(MAP_WIDTH, MAP_HEIGHT).build(2, &prefabs, &mut seed.level_rng(2));
+        let second =
+            MapBuilder::new(MAP_WIDTH, MAP_HEIGHT).build(2, &prefabs, &mut seed.level_rng(2));
+
+        assert_eq!(first.map, second.map);
+        assert_eq!(first.player_start, second.player_start);
+        assert_eq!(first.monster_spawns, second.monster_spawns);
+    }
+
+    #[test]
+    fn test_levels_differ() {
+        let seed = RunSeed(1234);
+        let prefabs = Prefabs::load().unwrap();
+
+        let first =
+            MapBuilder::new(MAP_WIDTH, MAP_HEIGHT).build(0, &prefabs, &mut seed.level_rng(0));
+        let second =
+            MapBuilder::new(MAP_WIDTH, MAP_HEIGHT).build(1, &prefabs, &mut seed.level_rng(1));
+
+        assert_ne!(first.map, second.map);
+    }
+}
@@ -0,0 +1,373 @@
+use std::{error::Error, fmt, fs, io, path::Path};
+
+use legion::serialize::{Canon, Registry};
+use ron::ser::PrettyConfig;
+use serde::{
+    de::{self, DeserializeSeed, SeqAccess, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+use crate::prelude::*;
+
+pub const SAVE_FILE: &str = "savegame.ron";
+
+/// The resources that make up a game in progress, alongside the `World` itself.
+#[derive(Clone, Serialize, Deserialize)]
+pub struct SavedResources {
+    pub seed: RunSeed,
+    pub rng: RandomNumberGenerator,
+    pub map: Map,
+    pub camera: Camera,
+    pub turn_state: TurnState,
+    pub theme: ThemeId,
+    #[serde(default)]
+    pub levels: LevelStore,
+    #[serde(default)]
+    pub log: MessageLog,
+}
+
+impl SavedResources {
+    #[allow(clippy::borrowed_box)]
+    fn from_resources(resources: &Resources) -> Self {
+        Self {
+            seed: *resources.get::<RunSeed>().unwrap(),
+            rng: resources.get::<RandomNumberGenerator>().unwrap().clone(),
+            map: resources.get::<Map>().unwrap().clone(),
+            camera: *resources.get::<Camera>().unwrap(),
+            turn_state: *resources.get::<TurnState>().unwrap(),
+            theme: resources.get::<Box<dyn MapTheme>>().unwrap().id(),
+            levels: resources
+                .get::<LevelStore>()
+                .map(|levels| levels.clone())
+                .unwrap_or_default(),
+            log: resources
+                .get::<MessageLog>()
+                .map(|log| log.clone())
+                .unwrap_or_default(),
+        }
+    }
+
+    fn into_resources(self) -> Resources {
+        let mut resources = Resources::default();
+        resources.insert(self.seed);
+        resources.insert(self.rng);
+        resources.insert(self.map);
+        resources.insert(self.camera);
+        resources.insert(self.turn_state);
+        resources.insert(self.theme.theme());
+        resources.insert(self.levels);
+        resources.insert(self.log);
+        resources.insert(InventoryScreen::default());
+        resources.insert(Targeting::default());
+        resources.insert(FlowField::default());
+        resources.insert(SpatialIndex::default());
+        resources
+    }
+}
+
+/// Every component that can live in the world has to be registered here, or saving will fail.
+fn registry() -> Registry<String> {
+    let mut registry = Registry::<String>::new();
+    registry.register::<Point>("Point".to_string());
+    registry.register::<StoredOnLevel>("StoredOnLevel".to_string());
+    registry.register::<Render>("Render".to_string());
+    registry.register::<Player>("Player".to_string());
+    registry.register::<Enemy>("Enemy".to_string());
+    registry.register::<MovingRandomly>("MovingRandomly".to_string());
+    registry.register::<WantsToMove>("WantsToMove".to_string());
+    registry.register::<WantsToCloseDoor>("WantsToCloseDoor".to_string());
+    registry.register::<WantsToActivateItem>("WantsToActivateItem".to_string());
+    registry.register::<Health>("Health".to_string());
+    registry.register::<Name>("Name".to_string());
+    registry.register::<WantsToAttack>("WantsToAttack".to_string());
+    registry.register::<ChasingPlayer>("ChasingPlayer".to_string());
+    registry.register::<LastSeenPlayer>("LastSeenPlayer".to_string());
+    registry.register::<Guarding>("Guarding".to_string());
+    registry.register::<Cowardly>("Cowardly".to_string());
+    registry.register::<FleesBelow>("FleesBelow".to_string());
+    registry.register::<LyingInWait>("LyingInWait".to_string());
+    registry.register::<Item>("Item".to_string());
+    registry.register::<AmuletOfYala>("AmuletOfYala".to_string());
+    registry.register::<FieldOfView>("FieldOfView".to_string());
+    registry.register::<Carried>("Carried".to_string());
+    registry.register::<AreaOfEffect>("AreaOfEffect".to_string());
+    registry.register::<TargetedAt>("TargetedAt".to_string());
+    registry.register::<Damage>("Damage".to_string());
+    registry.register::<Armor>("Armor".to_string());
+    registry.register::<Ranged>("Ranged".to_string());
+    registry.register::<Ammo>("Ammo".to_string());
+    registry.register::<RangedAttack>("RangedAttack".to_string());
+    registry.register::<Accuracy>("Accuracy".to_string());
+    registry.register::<Evasion>("Evasion".to_string());
+    registry.register::<StatusEffects>("StatusEffects".to_string());
+    registry.register::<Provides>("Provides".to_string());
+    registry.register::<InflictsStatus>("InflictsStatus".to_string());
+    registry.register::<Equippable>("Equippable".to_string());
+    registry.register::<Equipped>("Equipped".to_string());
+    registry
+}
+
+pub fn save_exists() -> bool {
+    Path::new(SAVE_FILE).exists()
+}
+
+pub fn save_game(ecs: &World, resources: &Resources) -> Result<(), Box<dyn Error>> {
+    fs::write(SAVE_FILE, serialize_game(ecs, resources)?)?;
+    Ok(())
+}
+
+/// Loads the saved game, along with the save's text so a recording can start from it.
+pub fn load_game() -> Result<(World, Resources, String), Box<dyn Error>> {
+    let save = fs::read_to_string(SAVE_FILE)?;
+    let (ecs, resources) = deserialize_game(&save)?;
+    Ok((ecs, resources, save))
+}
+
+pub fn delete_save() -> io::Result<()> {
+    if save_exists() {
+        fs::remove_file(SAVE_FILE)?;
+    }
+    Ok(())
+}
+
+pub fn serialize_game(ecs: &World, resources: &Resources) -> Result<String, Box<dyn Error>> {
+    let registry = registry();
+    let canon = Canon::default();
+    // Messages are tagged with `()`, and legion keeps their archetypes around even once they're
+    // all gone, so they have to be filtered out or every save after the first turn fails.
+    let world = ecs.as_serializable(!component::<()>(), &registry, &canon);
+    let saved = SavedResources::from_resources(resources);
+
+    Ok(ron::ser::to_string_pretty(
+        &(world, saved),
+        PrettyConfig::default(),
+    )?)
+}
+
+pub fn deserialize_game(save: &str) -> Result<(World, Resources), Box<dyn Error>> {
+    let registry = registry();
+    let canon = Canon::default();
+    let mut deserializer = ron::Deserializer::from_str(save)?;
+    let (world, saved) = SaveGameSeed {
+        registry: &registry,
+        canon: &canon,
+    }
+    .deserialize(&mut deserializer)?;
+    deserializer.end()?;
+
+    Ok((world, saved.into_resources()))
+}
+
+/// Legion can only deserialize a world through a `DeserializeSeed`, so the save file's
+/// `(world, resources)` tuple has to be visited by hand.
+#[derive(Clone, Copy)]
+struct SaveGameSeed<'a> {
+    registry: &'a Registry<String>,
+    canon: &'a Canon,
+}
+
+impl<'de, 'a> DeserializeSeed<'de> for SaveGameSeed<'a> {
+    type Value = (World, SavedResources);
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_tuple(2, self)
+    }
+}
+
+impl<'de, 'a> Visitor<'de> for SaveGameSeed<'a> {
+    type Value = (World, SavedResources);
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a saved world followed by its resources")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<Self::Value, A::Error> {
+        let world = seq
+            .next_element_seed(self.registry.as_deserialize(self.canon))?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        let saved = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))?;
+
+        Ok((world, saved))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use empty::EmptyArchitect;
+
+    fn build_game() -> (World, Resources) {
+        let mut ecs = World::default();
+        let mut resources = Resources::default();
+        let seed = RunSeed(1);
+        let mut rng = seed.level_rng(0);
+        let mb = EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng);
+
+        let player = spawn_player(&mut ecs, mb.player_start);
+        ecs.push((
+            Item,
+            Name("Healing Potion".to_string()),
+            Provides(vec![ProvidedEffect::new("Healing", 6)]),
+            Carried(player),
+        ));
+        ecs.push((
+            Enemy,
+            ChasingPlayer,
+            Point::new(3, 4),
+            Health::new(2),
+            FieldOfView::new(6),
+            Name("Orc".to_string()),
+        ));
+
+        resources.insert(seed);
+        resources.insert(rng);
+        resources.insert(mb.map);
+        resources.insert(Camera::new(mb.player_start));
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(ThemeId::Forest.theme());
+
+        (ecs, resources)
+    }
+
+    /// A run that's been down to level one and back, with gear on and a message in the log.
+    fn started_game() -> Game {
+        let mut game = Game::new().unwrap();
+        game.start(RunSeed(1));
+        for turn_state in [TurnState::NextLevel, TurnState::PreviousLevel] {
+            game.resources.insert(turn_state);
+            game.tick(None, Point::zero());
+        }
+
+        let player = <Entity>::query()
+            .filter(component::<Player>())
+            .iter(&game.ecs)
+            .copied()
+            .next()
+            .unwrap();
+        game.ecs.push((
+            Item,
+            Name("Healing Potion".to_string()),
+            Provides(vec![ProvidedEffect::new("Healing", 6)]),
+            Carried(player),
+        ));
+        game.ecs.push((
+            Item,
+            Name("Leather Armor".to_string()),
+            Armor(1),
+            Equippable(EquipmentSlot::Body),
+            Equipped(EquipmentSlot::Body),
+            Carried(player),
+        ));
+        game.resources
+            .get_mut::<MessageLog>()
+            .unwrap()
+            .add(MessageCategory::Info, "Welcome back.");
+
+        game
+    }
+
+    fn carried_items(ecs: &World) -> Vec<(String, String, Option<Equipped>)> {
+        let mut items: Vec<_> = <(&Name, &Carried, Option<&Equipped>)>::query()
+            .iter(ecs)
+            .map(|(name, carried, equipped)| {
+                let owner = ecs.entry_ref(carried.0).unwrap();
+                let owner = if owner.get_component::<Player>().is_ok() {
+                    "Player"
+                } else {
+                    "Other"
+                };
+                (name.0.clone(), owner.to_string(), equipped.copied())
+            })
+            .collect();
+        items.sort_by(|a, b| a.0.cmp(&b.0));
+        items
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let game = started_game();
+        let (ecs, resources) = (&game.ecs, &game.resources);
+
+        let save = serialize_game(ecs, resources).unwrap();
+        let (loaded_ecs, loaded_resources) = deserialize_game(&save).unwrap();
+
+        assert_eq!(ecs.len(), loaded_ecs.len());
+
+        let saved = SavedResources::from_resources(resources);
+        let loaded = SavedResources::from_resources(&loaded_resources);
+        assert_eq!(saved.seed, loaded.seed);
+        assert_eq!(saved.map, loaded.map);
+        assert_eq!(saved.camera, loaded.camera);
+        assert_eq!(saved.turn_state, loaded.turn_state);
+        assert_eq!(saved.theme, loaded.theme);
+        assert_eq!(saved.levels, loaded.levels);
+        assert_ne!(loaded.levels, LevelStore::default());
+        assert_eq!(saved.log, loaded.log);
+        assert!(!loaded.log.visible(10).is_empty());
+
+        let rolls = |mut rng: RandomNumberGenerator| {
+            (0..10).map(|_| rng.roll_dice(1, 100)).collect::<Vec<_>>()
+        };
+        assert_eq!(rolls(saved.rng), rolls(loaded.rng));
+
+        let player = |ecs: &World| {
+            <(&Player, &Point, &Health, &FieldOfView)>::query()
+                .iter(ecs)
+                .map(|(player, pos, health, fov)| (*player, *pos, *health, fov.clone()))
+                .next()
+                .unwrap()
+        };
+        assert_eq!(player(ecs), player(&loaded_ecs));
+
+        let enemies = |ecs: &World| {
+            let mut enemies = <(&Name, &Point, &Health)>::query()
+                .filter(component::<Enemy>())
+                .iter(ecs)
+                .map(|(name, pos, health)| (name.clone(), *pos, *health))
+                .collect::<Vec<_>>();
+            enemies.sort_by_key(|(name, pos, _)| (name.0.clone(), pos.x, pos.y));
+            enemies
+        };
+        assert!(!enemies(ecs).is_empty());
+        assert_eq!(enemies(ecs), enemies(&loaded_ecs));
+
+        assert_eq!(carried_items(ecs), carried_items(&loaded_ecs));
+        assert_eq!(
+            carried_items(&loaded_ecs),
+            vec![
+                ("Healing Potion".to_string(), "Player".to_string(), None),
+                (
+                    "Leather Armor".to_string(),
+                    "Player".to_string(),
+                    Some(Equipped(EquipmentSlot::Body))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_saving_after_messages_were_handled() {
+        let (mut ecs, resources) = build_game();
+        let player = <Entity>::query()
+            .filter(component::<Player>())
+            .iter(&ecs)
+            .copied()
+            .next()
+            .unwrap();
+        let message = ecs.push((
+            (),
+            WantsToMove {
+                entity: player,
+                destination: Point::zero(),
+            },
+        ));
+        ecs.remove(message);
+
+        let save = serialize_game(&ecs, &resources).unwrap();
+        let (loaded_ecs, _) = deserialize_game(&save).unwrap();
+
+        assert_eq!(ecs.len(), loaded_ecs.len());
+    }
+}
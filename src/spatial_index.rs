@@ -0,0 +1,95 @@
+use crate::prelude::*;
+
+/// Which entities are on each tile, so finding what's at a position doesn't mean scanning every
+/// entity. Creatures, meaning anything with `Health`, block their tile and there's only ever one
+/// of them on it. The `spatial_index` system rebuilds it at the start of every turn, and
+/// `movement` keeps it up to date as things move.
+#[derive(Default)]
+pub struct SpatialIndex {
+    width: i32,
+    height: i32,
+    tiles: Vec<Vec<Entity>>,
+    creatures: Vec<Option<Entity>>,
+}
+
+impl SpatialIndex {
+    /// Indexes every entity with a `Point` afresh. `ecs` has to be able to read `Point` and
+    /// `Health`.
+    pub fn rebuild(&mut self, map: &Map, ecs: &impl EntityStore) {
+        let num_tiles = map.tiles.len();
+        self.width = map.width;
+        self.height = map.height;
+        self.tiles.resize_with(num_tiles, Vec::new);
+        self.tiles.iter_mut().for_each(Vec::clear);
+        self.creatures.clear();
+        self.creatures.resize(num_tiles, None);
+
+        for (entity, pos, health) in <(Entity, &Point, Option<&Health>)>::query().iter(ecs) {
+            if let Some(idx) = self.try_idx(*pos) {
+                self.tiles[idx].push(*entity);
+                if health.is_some() {
+                    self.creatures[idx] = Some(*entity);
+                }
+            }
+        }
+    }
+
+    /// Everything on `pos`, in no particular order.
+    pub fn entities_at(&self, pos: Point) -> &[Entity] {
+        self.try_idx(pos)
+            .map(|idx| self.tiles[idx].as_slice())
+            .unwrap_or_default()
+    }
+
+    /// The creature standing on `pos`, if there is one.
+    pub fn creature_at(&self, pos: Point) -> Option<Entity> {
+        self.try_idx(pos).and_then(|idx| self.creatures[idx])
+    }
+
+    /// Records `entity` moving from `from` to `to`.
+    pub fn move_entity(&mut self, entity: Entity, from: Point, to: Point) {
+        let (from, to) = match (self.try_idx(from), self.try_idx(to)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => return,
+        };
+        self.tiles[from].retain(|other| *other != entity);
+        self.tiles[to].push(entity);
+        if self.creatures[from] == Some(entity) {
+            self.creatures[from] = None;
+            self.creatures[to] = Some(entity);
+        }
+    }
+
+    fn try_idx(&self, pos: Point) -> Option<usize> {
+        if pos.x < 0 || pos.x >= self.width || pos.y < 0 || pos.y >= self.height {
+            None
+        } else {
+            Some(((pos.y * self.width) + pos.x) as usize)
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_creatures_block_their_tile_and_items_do_not() {
+        let map = Map::new(10, 10);
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::new(1, 1));
+        let item = world.push((Item, Point::new(2, 2)));
+        let mut index = SpatialIndex::default();
+        index.rebuild(&map, &world);
+
+        assert_eq!(index.creature_at(Point::new(1, 1)), Some(player));
+        assert_eq!(index.creature_at(Point::new(2, 2)), None);
+        assert_eq!(index.entities_at(Point::new(2, 2)), &[item]);
+        assert!(index.entities_at(Point::new(-1, 20)).is_empty());
+
+        index.move_entity(player, Point::new(1, 1), Point::new(2, 2));
+        assert_eq!(index.creature_at(Point::new(1, 1)), None);
+        assert_eq!(index.creature_at(Point::new(2, 2)), Some(player));
+        assert_eq!(index.entities_at(Point::new(2, 2)).len(), 2);
+    }
+}
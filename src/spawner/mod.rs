@@ -13,7 +13,8 @@ pub fn spawn_player(ecs: &mut World, pos: Point) -> Entity {
         },
         Health::new(10),
         FieldOfView::new(8),
-        Damage(1),
+        Damage(Dice::flat(1)),
+        Accuracy(2),
     ))
 }
 
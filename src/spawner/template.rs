@@ -1,9 +1,23 @@
 use crate::prelude::*;
-use std::{collections::HashSet, fs::File};
+use std::{
+    collections::HashSet,
+    fmt, fs,
+    path::{Path, PathBuf},
+};
 
-use ron::de::from_reader;
 use serde::*;
 
+/// The template file, inside `resource_dir()`.
+pub const TEMPLATE_FILE: &str = "template.ron";
+
+/// The names `inflicts` accepts, and the statuses they stand for.
+const STATUSES: [(&str, StatusKind); 4] = [
+    ("Poison", StatusKind::Poison),
+    ("Regeneration", StatusKind::Regeneration),
+    ("Confusion", StatusKind::Confusion),
+    ("Slow", StatusKind::Slow),
+];
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct Template {
     pub entity_type: EntityType,
@@ -12,8 +26,25 @@ pub struct Template {
     pub name: String,
     pub glyph: char,
     pub provides: Option<Vec<(String, i32)>>,
+    /// Status effects a monster's hits put on its victim, with how many turns they last.
+    pub inflicts: Option<Vec<(String, i32)>>,
     pub hp: Option<i32>,
-    pub base_damage: Option<i32>,
+    /// How a monster behaves. Monsters chase the player unless told otherwise.
+    pub ai: Option<AiProfile>,
+    /// How far a monster can see. Defaults to `DEFAULT_VISION`.
+    pub vision: Option<i32>,
+    /// A monster runs away while its hp is below this. Cowards flee at half health unless they
+    /// say otherwise.
+    pub flee_below: Option<i32>,
+    pub base_damage: Option<Dice>,
+    pub armor: Option<i32>,
+    pub accuracy: Option<i32>,
+    pub evasion: Option<i32>,
+    pub slot: Option<EquipmentSlot>,
+    pub range: Option<i32>,
+    /// How far around the target an item's effect spreads.
+    pub radius: Option<i32>,
+    pub ammo: Option<i32>,
 }
 
 #[derive(Clone, Deserialize, Debug, PartialEq)]
@@ -22,15 +53,158 @@ pub enum EntityType {
     Item,
 }
 
+/// The behaviours a monster's `ai` can pick from, each run by its own system.
+#[derive(Clone, Copy, Deserialize, Debug, PartialEq)]
+pub enum AiProfile {
+    Chaser,
+    Wanderer,
+    Guard,
+    Coward,
+    Ambusher,
+}
+
+pub const DEFAULT_VISION: i32 = 6;
+
 #[derive(Clone, Deserialize, Debug)]
 pub struct Templates {
     pub entities: Vec<Template>,
 }
 
+/// Something wrong with a single template.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TemplateProblem {
+    EnemyWithoutHp,
+    NoVision,
+    DuplicateName,
+    ZeroFrequency,
+    NoLevels,
+    Effect(EffectError),
+    UnknownStatus(String),
+}
+
+impl fmt::Display for TemplateProblem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateProblem::EnemyWithoutHp => write!(f, "enemies need an hp"),
+            TemplateProblem::NoVision => write!(f, "vision has to be at least 1"),
+            TemplateProblem::DuplicateName => write!(f, "another template has the same name"),
+            TemplateProblem::ZeroFrequency => write!(f, "a frequency of 0 means it never spawns"),
+            TemplateProblem::NoLevels => write!(f, "levels is empty, so it never spawns"),
+            TemplateProblem::Effect(err) => write!(f, "{}", err),
+            TemplateProblem::UnknownStatus(name) => {
+                let valid: Vec<&str> = STATUSES.iter().map(|(name, _)| *name).collect();
+                write!(
+                    f,
+                    "unknown status \"{}\". Valid statuses are: {}",
+                    name,
+                    valid.join(", ")
+                )
+            }
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TemplateIssue {
+    pub template: String,
+    pub problem: TemplateProblem,
+}
+
+#[derive(Debug)]
+pub enum TemplateError {
+    /// The file couldn't be read, or isn't valid RON.
+    Load { file: PathBuf, message: String },
+    /// The file parsed, but some of its templates don't make sense.
+    Invalid {
+        file: PathBuf,
+        issues: Vec<TemplateIssue>,
+    },
+}
+
+impl fmt::Display for TemplateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TemplateError::Load { file, message } => write!(f, "{}: {}", file.display(), message),
+            TemplateError::Invalid { file, issues } => {
+                write!(f, "{}: {} invalid templates", file.display(), issues.len())?;
+                for issue in issues {
+                    write!(f, "\n  \"{}\": {}", issue.template, issue.problem)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for TemplateError {}
+
 impl Templates {
-    pub fn load() -> Self {
-        let file = File::open("resources/template.ron").expect("Failed to load templates file");
-        from_reader(file).expect("Unable to deserialize templates")
+    pub fn load() -> Result<Self, TemplateError> {
+        Self::load_file(&resource_dir().join(TEMPLATE_FILE))
+    }
+
+    pub fn load_file(file: &Path) -> Result<Self, TemplateError> {
+        let error = |message: String| TemplateError::Load {
+            file: file.to_path_buf(),
+            message,
+        };
+        let text = fs::read_to_string(file).map_err(|err| error(err.to_string()))?;
+        let templates: Self = ron::from_str(&text).map_err(|err| error(err.to_string()))?;
+
+        let issues = templates.validate();
+        if !issues.is_empty() {
+            return Err(TemplateError::Invalid {
+                file: file.to_path_buf(),
+                issues,
+            });
+        }
+        Ok(templates)
+    }
+
+    /// Everything that would stop a template spawning properly, in file order, so a typo fails at
+    /// load time rather than halfway through a run.
+    pub fn validate(&self) -> Vec<TemplateIssue> {
+        let mut issues = Vec::new();
+        let mut names = HashSet::new();
+        for template in self.entities.iter() {
+            let mut problem = |problem| {
+                issues.push(TemplateIssue {
+                    template: template.name.clone(),
+                    problem,
+                })
+            };
+
+            if !names.insert(template.name.as_str()) {
+                problem(TemplateProblem::DuplicateName);
+            }
+            if template.entity_type == EntityType::Enemy && template.hp.is_none() {
+                problem(TemplateProblem::EnemyWithoutHp);
+            }
+            if template.vision.is_some_and(|vision| vision < 1) {
+                problem(TemplateProblem::NoVision);
+            }
+            if template.frequency < 1 {
+                problem(TemplateProblem::ZeroFrequency);
+            }
+            if template.levels.is_empty() {
+                problem(TemplateProblem::NoLevels);
+            }
+            for (name, amount) in template.provides.iter().flatten() {
+                if let Err(err) = check_effect(name, *amount) {
+                    problem(TemplateProblem::Effect(err));
+                }
+            }
+            for (name, _) in template.inflicts.iter().flatten() {
+                if status_kind(name).is_none() {
+                    problem(TemplateProblem::UnknownStatus(name.clone()));
+                }
+            }
+        }
+        issues
+    }
+
+    pub fn find(&self, name: &str) -> Option<&Template> {
+        self.entities.iter().find(|template| template.name == name)
     }
 
     pub fn spawn_entities(
@@ -40,9 +214,52 @@ impl Templates {
         rng: &mut RandomNumberGenerator,
         level: usize,
         spawn_points: &[Point],
+    ) {
+        self.spawn_random(ecs, resources, rng, spawn_points, |template| {
+            template.levels.contains(&level)
+        });
+    }
+
+    pub fn spawn_items(
+        &self,
+        ecs: &mut World,
+        resources: &mut Resources,
+        rng: &mut RandomNumberGenerator,
+        level: usize,
+        spawn_points: &[Point],
+    ) {
+        self.spawn_random(ecs, resources, rng, spawn_points, |template| {
+            template.levels.contains(&level) && template.entity_type == EntityType::Item
+        });
+    }
+
+    /// Spawns exactly the named templates, whatever level they'd normally turn up on.
+    pub fn spawn_named(
+        &self,
+        ecs: &mut World,
+        resources: &mut Resources,
+        spawns: &[(Point, String)],
+    ) {
+        let mut commands = CommandBuffer::new(ecs);
+        for (spawn, name) in spawns.iter() {
+            let template = self
+                .find(name)
+                .unwrap_or_else(|| panic!("No template named {}", name));
+            self.spawn_entity(*spawn, template, &mut commands);
+        }
+        commands.flush(ecs, resources);
+    }
+
+    fn spawn_random(
+        &self,
+        ecs: &mut World,
+        resources: &mut Resources,
+        rng: &mut RandomNumberGenerator,
+        spawn_points: &[Point],
+        filter: impl Fn(&Template) -> bool,
     ) {
         let mut available_entities = Vec::new();
-        for template in self.entities.iter().filter(|e| e.levels.contains(&level)) {
+        for template in self.entities.iter().filter(|e| filter(e)) {
             for _ in 0..template.frequency {
                 available_entities.push(template);
             }
@@ -70,28 +287,142 @@ impl Templates {
         match template.entity_type {
             EntityType::Enemy => {
                 commands.add_component(entity, Enemy);
-                commands.add_component(entity, FieldOfView::new(6));
-                commands.add_component(entity, ChasingPlayer);
-                commands.add_component(entity, Health::new(template.hp.unwrap()));
+                let vision = template.vision.unwrap_or(DEFAULT_VISION);
+                commands.add_component(entity, FieldOfView::new(vision));
+                let ai = template.ai.unwrap_or(AiProfile::Chaser);
+                let coward_threshold = template.hp.map(|hp| hp / 2 + 1);
+                let flee_below = match ai {
+                    AiProfile::Coward => template.flee_below.or(coward_threshold),
+                    _ => template.flee_below,
+                };
+                if let Some(flee_below) = flee_below {
+                    commands.add_component(entity, FleesBelow(flee_below));
+                }
+                match ai {
+                    AiProfile::Chaser => commands.add_component(entity, ChasingPlayer),
+                    AiProfile::Wanderer => commands.add_component(entity, MovingRandomly),
+                    AiProfile::Guard => commands.add_component(entity, Guarding),
+                    AiProfile::Coward => commands.add_component(entity, Cowardly),
+                    AiProfile::Ambusher => commands.add_component(entity, LyingInWait),
+                }
+                // `validate` turns away enemies without any.
+                commands.add_component(entity, Health::new(template.hp.unwrap_or(1)));
             }
             EntityType::Item => commands.add_component(entity, Item),
         }
 
         if let Some(effects) = &template.provides {
-            for (provides, n) in effects.iter() {
-                match provides.as_str() {
-                    "Healing" => commands.add_component(entity, ProvidesHealing { amount: *n }),
-                    "MagicMap" => commands.add_component(entity, ProvidesDungeonMap),
-                    _ => panic!("Error, cannot provide component {}", provides),
-                }
-            }
+            let effects = effects
+                .iter()
+                .map(|(name, amount)| ProvidedEffect::new(name, *amount))
+                .collect();
+            commands.add_component(entity, Provides(effects));
+        }
+
+        if let Some(effects) = &template.inflicts {
+            let statuses = effects
+                .iter()
+                .filter_map(|(name, turns)| Some(StatusEffect::new(status_kind(name)?, *turns)))
+                .collect();
+            commands.add_component(entity, InflictsStatus(statuses));
         }
 
         if let Some(damage) = template.base_damage {
             commands.add_component(entity, Damage(damage));
-            if template.entity_type == EntityType::Item {
-                commands.add_component(entity, Weapon);
-            }
         }
+
+        if let Some(armor) = template.armor {
+            commands.add_component(entity, Armor(armor));
+        }
+
+        if let Some(accuracy) = template.accuracy {
+            commands.add_component(entity, Accuracy(accuracy));
+        }
+
+        if let Some(evasion) = template.evasion {
+            commands.add_component(entity, Evasion(evasion));
+        }
+
+        if let Some(range) = template.range {
+            commands.add_component(entity, Ranged { range });
+        }
+
+        if let Some(radius) = template.radius {
+            commands.add_component(entity, AreaOfEffect(radius));
+        }
+
+        if let Some(ammo) = template.ammo {
+            commands.add_component(entity, Ammo(ammo));
+        }
+
+        if let Some(slot) = template.slot {
+            commands.add_component(entity, Equippable(slot));
+        }
+    }
+}
+
+fn status_kind(name: &str) -> Option<StatusKind> {
+    STATUSES
+        .iter()
+        .find(|(status, _)| *status == name)
+        .map(|(_, kind)| *kind)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn issue(template: &str, problem: TemplateProblem) -> TemplateIssue {
+        TemplateIssue {
+            template: template.to_string(),
+            problem,
+        }
+    }
+
+    #[test]
+    fn test_shipped_templates_are_valid() {
+        let templates = Templates::load().unwrap_or_else(|err| panic!("{}", err));
+
+        assert!(!templates.entities.is_empty());
+    }
+
+    #[test]
+    fn test_every_problem_is_reported() {
+        let templates: Templates = ron::from_str(
+            r#"Templates(entities: [
+                Template(entity_type: Enemy, name: "Orc", glyph: 'o', levels: [0], frequency: 1),
+                Template(entity_type: Item, name: "Orc", glyph: 'o', levels: [], frequency: 0),
+                Template(
+                    entity_type: Item,
+                    name: "Odd Potion",
+                    glyph: '!',
+                    levels: [0],
+                    frequency: 1,
+                    provides: Some([("Levitation", 3)]),
+                    inflicts: Some([("Sleep", 2)]),
+                ),
+            ])"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            templates.validate(),
+            vec![
+                issue("Orc", TemplateProblem::EnemyWithoutHp),
+                issue("Orc", TemplateProblem::DuplicateName),
+                issue("Orc", TemplateProblem::ZeroFrequency),
+                issue("Orc", TemplateProblem::NoLevels),
+                issue(
+                    "Odd Potion",
+                    TemplateProblem::Effect(EffectError::Unknown {
+                        name: "Levitation".to_string()
+                    })
+                ),
+                issue(
+                    "Odd Potion",
+                    TemplateProblem::UnknownStatus("Sleep".to_string())
+                ),
+            ]
+        );
     }
 }
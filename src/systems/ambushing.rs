@@ -0,0 +1,60 @@
+use super::chasing::is_adjacent;
+use super::fleeing::is_fleeing;
+use crate::prelude::*;
+
+/// Ambushers keep still until the player walks up to them, then strike and give chase.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(LyingInWait)]
+#[read_component(Health)]
+#[read_component(FleesBelow)]
+pub fn ambushing(ecs: &SubWorld, commands: &mut CommandBuffer) {
+    let (player, player_pos) = <(Entity, &Point)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, pos)| (*entity, *pos))
+        .next()
+        .unwrap();
+
+    for (entity, _) in <(Entity, &Point)>::query()
+        .filter(component::<LyingInWait>())
+        .iter(ecs)
+        .filter(|(entity, pos)| is_adjacent(**pos, player_pos) && !is_fleeing(ecs, **entity))
+    {
+        commands.push((
+            (),
+            WantsToAttack {
+                attacker: *entity,
+                victim: player,
+            },
+        ));
+        commands.remove_component::<LyingInWait>(*entity);
+        commands.add_component(*entity, ChasingPlayer);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_ambushers_spring_when_the_player_is_adjacent() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let player = spawn_player(&mut world, Point::new(5, 5));
+        let ambusher = world.push((Enemy, LyingInWait, Point::new(5, 7)));
+        let mut schedule = Schedule::builder().add_system(ambushing_system()).build();
+
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(<&WantsToAttack>::query().iter(&world).count(), 0);
+
+        world.entry(player).unwrap().add_component(Point::new(5, 6));
+        schedule.execute(&mut world, &mut resources);
+
+        assert_eq!(<&WantsToAttack>::query().iter(&world).count(), 1);
+        let entry = world.entry_ref(ambusher).unwrap();
+        assert!(entry.get_component::<LyingInWait>().is_err());
+        assert!(entry.get_component::<ChasingPlayer>().is_ok());
+    }
+}
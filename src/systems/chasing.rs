@@ -1,70 +1,142 @@
+use super::fleeing::is_fleeing;
 use crate::prelude::*;
 
+/// How many turns a monster looks around where it lost sight of the player before giving up.
+pub const SEARCH_TURNS: i32 = 5;
+
+/// Chasers go straight for a player they can see. Once they lose sight of them, they head for
+/// where the player was last seen and search around there for a while.
 #[system]
 #[read_component(Point)]
 #[read_component(Player)]
 #[read_component(Health)]
 #[read_component(ChasingPlayer)]
 #[read_component(FieldOfView)]
-pub fn chasing(ecs: &SubWorld, #[resource] map: &Map, commands: &mut CommandBuffer) {
-    let mut movers = <(Entity, &Point, &ChasingPlayer, &FieldOfView)>::query();
-    let mut positions = <(Entity, &Point, &Health)>::query();
+#[read_component(LastSeenPlayer)]
+#[read_component(FleesBelow)]
+pub fn chasing(
+    ecs: &SubWorld,
+    #[resource] map: &Map,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] flow: &FlowField,
+    #[resource] index: &SpatialIndex,
+    commands: &mut CommandBuffer,
+) {
+    let mut movers = <(
+        Entity,
+        &Point,
+        &ChasingPlayer,
+        &FieldOfView,
+        Option<&LastSeenPlayer>,
+    )>::query();
     let mut player = <(&Point, &Player)>::query();
 
-    let player_pos = player.iter(ecs).next().unwrap().0;
-    let player_idx = map_idx(player_pos.x, player_pos.y);
-    let search_targets = vec![player_idx];
-
-    let djikstra_map = DijkstraMap::new(
-        SCREEN_WIDTH,
-        SCREEN_HEIGHT,
-        &search_targets,
-        map,
-        MAX_FLOWMAP_DISTANCE,
-    );
+    let player_pos = *player.iter(ecs).next().unwrap().0;
+    let djikstra_map = match flow.distances() {
+        Some(distances) => distances,
+        None => return,
+    };
 
-    for (entity, pos, _, fov) in movers.iter(ecs) {
-        if !fov.visible_tiles.contains(player_pos) {
+    for (entity, pos, _, fov, memory) in movers.iter(ecs) {
+        if is_fleeing(ecs, *entity) {
             continue;
         }
-        let idx = map_idx(pos.x, pos.y);
-        if let Some(destination) = DijkstraMap::find_lowest_exit(&djikstra_map, idx, map) {
-            let distance = DistanceAlg::Pythagoras.distance2d(*pos, *player_pos);
-            let destination = if distance > 1.2 {
-                map.index_to_point2d(destination)
-            } else {
-                *player_pos
+        if fov.visible_tiles.contains(&player_pos) {
+            let memory = LastSeenPlayer {
+                pos: player_pos,
+                search_turns: SEARCH_TURNS,
             };
-
-            let mut attacked = false;
-
-            positions
-                .iter(ecs)
-                .filter(|(_, target_pos, _)| **target_pos == destination)
-                .for_each(|(victim, _, _)| {
-                    if ecs
-                        .entry_ref(*victim)
-                        .unwrap()
-                        .get_component::<Player>()
-                        .is_ok()
-                    {
-                        println!("Attacking player?");
-                        let victim = *victim;
-                        let attacker = *entity;
-                        commands.push(((), WantsToAttack { attacker, victim }));
-                        attacked = true;
-                    }
-                });
-
-            if !attacked {
-                commands.push((
-                    (),
-                    WantsToMove {
-                        entity: *entity,
-                        destination,
-                    },
-                ));
+            commands.add_component(*entity, memory);
+            if let Some(destination) = step_towards(map, djikstra_map, *pos, player_pos) {
+                attack_or_move(ecs, index, commands, *entity, destination);
             }
+            continue;
+        }
+
+        let memory = match memory {
+            Some(memory) => *memory,
+            None => continue,
+        };
+        let path = a_star_search(
+            map.point2d_to_index(*pos),
+            map.point2d_to_index(memory.pos),
+            map,
+        );
+        let destination = if path.success && path.steps.len() > 1 {
+            map.index_to_point2d(path.steps[1])
+        } else if memory.search_turns > 0 {
+            // Either it's arrived or it can't get there, so it looks around where it is.
+            let search_turns = memory.search_turns - 1;
+            commands.add_component(
+                *entity,
+                LastSeenPlayer {
+                    search_turns,
+                    ..memory
+                },
+            );
+            *pos + Map::directions()[rng.range(0, 4)]
+        } else {
+            commands.remove_component::<LastSeenPlayer>(*entity);
+            continue;
+        };
+        attack_or_move(ecs, index, commands, *entity, destination);
+    }
+}
+
+/// The next tile on the way from `pos` to `target`, which is `target` itself once they're next
+/// to each other.
+pub(super) fn step_towards(
+    map: &Map,
+    djikstra_map: &DijkstraMap,
+    pos: Point,
+    target: Point,
+) -> Option<Point> {
+    let idx = map.idx(pos.x, pos.y);
+    let destination = DijkstraMap::find_lowest_exit(djikstra_map, idx, map)?;
+    if is_adjacent(pos, target) {
+        Some(target)
+    } else {
+        Some(map.index_to_point2d(destination))
+    }
+}
+
+/// Whether `pos` is right next to `target`, close enough for a melee attack.
+pub(super) fn is_adjacent(pos: Point, target: Point) -> bool {
+    DistanceAlg::Pythagoras.distance2d(pos, target) < 1.2
+}
+
+/// Attacks the player if they're standing on `destination`, and moves there otherwise. The caller
+/// has to be able to read `Player`.
+pub(super) fn attack_or_move(
+    ecs: &SubWorld,
+    index: &SpatialIndex,
+    commands: &mut CommandBuffer,
+    entity: Entity,
+    destination: Point,
+) {
+    let victim = index.creature_at(destination).filter(|creature| {
+        ecs.entry_ref(*creature)
+            .is_ok_and(|entry| entry.get_component::<Player>().is_ok())
+    });
+
+    match victim {
+        Some(victim) => {
+            commands.push((
+                (),
+                WantsToAttack {
+                    attacker: entity,
+                    victim,
+                },
+            ));
+        }
+        None => {
+            commands.push((
+                (),
+                WantsToMove {
+                    entity,
+                    destination,
+                },
+            ));
         }
     }
 }
@@ -76,9 +148,14 @@ mod test {
     use self::empty::EmptyArchitect;
 
     use super::*;
+    use crate::systems::{flow_field_system, spatial_index_system};
 
     fn build_schedule() -> Schedule {
-        Schedule::builder().add_system(chasing_system()).build()
+        Schedule::builder()
+            .add_system(spatial_index_system())
+            .add_system(flow_field_system())
+            .add_system(chasing_system())
+            .build()
     }
 
     #[test]
@@ -94,8 +171,6 @@ mod test {
 
         state.step();
 
-        println!("{:?}", state.world);
-
         let WantsToMove {
             entity,
             destination,
@@ -108,6 +183,111 @@ mod test {
         assert_eq!(destination, &expected_destination);
     }
 
+    #[test]
+    fn test_chasing_around_wall() {
+        let mut state = StateFixture::default().with_schedule(build_schedule());
+        state.update(&mut |map: &mut Map| {
+            let idx = map.idx(1, 0);
+            map.tiles[idx] = TileType::Wall;
+        });
+
+        let mut enemy_fov = FieldOfView::new(6);
+        enemy_fov.visible_tiles.insert(Point::zero());
+        state
+            .world
+            .push((Enemy, ChasingPlayer, enemy_fov, Point::new(2, 0)));
+
+        state.step();
+
+        let WantsToMove { destination, .. } = <&WantsToMove>::query()
+            .iter(&state.world)
+            .next()
+            .expect("Expected enemy to move");
+
+        assert_eq!(destination, &Point::new(2, 1));
+    }
+
+    #[test]
+    fn test_seeing_the_player_is_remembered() {
+        let mut state = StateFixture::default().with_schedule(build_schedule());
+        let mut enemy_fov = FieldOfView::new(6);
+        enemy_fov.visible_tiles.insert(Point::zero());
+        let enemy = state
+            .world
+            .push((Enemy, ChasingPlayer, enemy_fov, Point::new(4, 0)));
+
+        state.step();
+
+        assert_eq!(
+            memory(&state, enemy),
+            Some(LastSeenPlayer {
+                pos: Point::zero(),
+                search_turns: SEARCH_TURNS
+            })
+        );
+    }
+
+    #[test]
+    fn test_chasers_head_for_where_the_player_was_last_seen() {
+        let mut state = StateFixture::default().with_schedule(build_schedule());
+        let last_seen = LastSeenPlayer {
+            pos: Point::new(2, 5),
+            search_turns: SEARCH_TURNS,
+        };
+        state.world.push((
+            Enemy,
+            ChasingPlayer,
+            FieldOfView::new(6),
+            last_seen,
+            Point::new(5, 5),
+        ));
+
+        state.step();
+
+        let WantsToMove { destination, .. } = <&WantsToMove>::query()
+            .iter(&state.world)
+            .next()
+            .expect("Expected enemy to move");
+        assert_eq!(destination, &Point::new(4, 5));
+    }
+
+    #[test]
+    fn test_chasers_search_for_a_while_then_give_up() {
+        let mut state = StateFixture::default().with_schedule(build_schedule());
+        let last_seen = LastSeenPlayer {
+            pos: Point::new(5, 5),
+            search_turns: 2,
+        };
+        let enemy = state.world.push((
+            Enemy,
+            ChasingPlayer,
+            FieldOfView::new(6),
+            last_seen,
+            Point::new(5, 5),
+        ));
+
+        state.step();
+        assert_eq!(
+            memory(&state, enemy).map(|memory| memory.search_turns),
+            Some(1)
+        );
+        state.step();
+        assert_eq!(
+            memory(&state, enemy).map(|memory| memory.search_turns),
+            Some(0)
+        );
+        assert_eq!(<&WantsToMove>::query().iter(&state.world).count(), 2);
+
+        state.step();
+        assert_eq!(memory(&state, enemy), None);
+        assert_eq!(<&WantsToMove>::query().iter(&state.world).count(), 2);
+    }
+
+    fn memory(state: &StateFixture, enemy: Entity) -> Option<LastSeenPlayer> {
+        let entry = state.world.entry_ref(enemy).ok()?;
+        entry.get_component::<LastSeenPlayer>().ok().copied()
+    }
+
     struct StateFixture {
         step_schedule: Schedule,
         world: World,
@@ -161,10 +341,15 @@ mod test {
 
     impl Default for StateFixture {
         fn default() -> Self {
-            let map_builder = EmptyArchitect {}.build(&mut RandomNumberGenerator::new());
+            let map_builder =
+                EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut RandomNumberGenerator::new());
             let camera = Camera::new(Point::zero());
 
-            Self::new(Schedule::builder().build())
+            let mut fixture = Self::new(Schedule::builder().build());
+            fixture.resources.insert(RandomNumberGenerator::seeded(1));
+            fixture.resources.insert(FlowField::default());
+            fixture.resources.insert(SpatialIndex::default());
+            fixture
                 .with_map(map_builder.map)
                 .with_camera(camera)
                 .with_player()
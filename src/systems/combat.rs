@@ -1,56 +1,443 @@
 use crate::prelude::*;
 
+/// What an attacker with no accuracy has to roll on a d20 to hit a victim with no evasion.
+const TO_HIT: i32 = 8;
+
+/// The dice rolled for one attack, kept so the log can show how the result came about.
+#[derive(Clone, Debug, PartialEq)]
+struct AttackRoll {
+    d20: i32,
+    accuracy: i32,
+    target: i32,
+    /// Each damage die alongside what it rolled. Empty if the attack missed.
+    damage: Vec<(Dice, i32)>,
+    defence: i32,
+}
+
+impl AttackRoll {
+    fn roll(
+        rng: &mut RandomNumberGenerator,
+        accuracy: i32,
+        evasion: i32,
+        dice: &[Dice],
+        defence: i32,
+    ) -> Self {
+        let mut roll = Self {
+            d20: rng.roll_dice(1, 20),
+            accuracy,
+            target: TO_HIT + evasion,
+            damage: Vec::new(),
+            defence,
+        };
+        if roll.hit() {
+            let critical = roll.is_critical();
+            roll.damage = dice
+                .iter()
+                .map(|dice| (*dice, dice.roll(rng, critical)))
+                .collect();
+        }
+        roll
+    }
+
+    /// A natural 20 always hits, and rolls its damage dice twice.
+    fn is_critical(&self) -> bool {
+        self.d20 == 20
+    }
+
+    fn hit(&self) -> bool {
+        self.is_critical() || self.d20 + self.accuracy >= self.target
+    }
+
+    fn damage(&self) -> i32 {
+        let rolled: i32 = self.damage.iter().map(|(_, rolled)| rolled).sum();
+        i32::max(rolled - self.defence, 0)
+    }
+
+    /// e.g. `d20 14+2 vs 8; 1d2+1d4 = 2+3; -1 armor`
+    fn breakdown(&self) -> String {
+        let mut breakdown = format!("d20 {}{:+} vs {}", self.d20, self.accuracy, self.target);
+        if self.is_critical() {
+            breakdown.push_str(" (critical)");
+        }
+        if self.hit() {
+            let dice: Vec<String> = self
+                .damage
+                .iter()
+                .map(|(dice, _)| dice.to_string())
+                .collect();
+            let rolls: Vec<String> = self
+                .damage
+                .iter()
+                .map(|(_, roll)| roll.to_string())
+                .collect();
+            breakdown.push_str(&format!("; {} = {}", dice.join("+"), rolls.join("+")));
+            if self.defence != 0 {
+                breakdown.push_str(&format!("; -{} armor", self.defence));
+            }
+        }
+        breakdown
+    }
+}
+
+/// Bows keep their place in the pack when they run dry, but throwables are gone once the last one
+/// has been thrown.
+fn use_ammo(ecs: &SubWorld, commands: &mut CommandBuffer, log: &mut MessageLog, weapon: Entity) {
+    let entry = match ecs.entry_ref(weapon) {
+        Ok(entry) => entry,
+        Err(_) => return,
+    };
+    if let Ok(Ammo(ammo)) = entry.get_component::<Ammo>() {
+        if *ammo > 1 || entry.get_component::<Equippable>().is_ok() {
+            commands.add_component(weapon, Ammo(ammo - 1));
+        } else {
+            commands.remove(weapon);
+            log.add(
+                MessageCategory::Item,
+                format!("That was the last of {}.", display_name(ecs, weapon)),
+            );
+        }
+    }
+}
+
+/// Shots roll only the weapon's damage, while melee attacks add up the attacker's own and its gear's.
+fn attack_dice(ecs: &SubWorld, attacker: Entity, ranged_weapon: Option<Entity>) -> Vec<Dice> {
+    match ranged_weapon {
+        Some(weapon) => ecs
+            .entry_ref(weapon)
+            .ok()
+            .and_then(|weapon| weapon.get_component::<Damage>().ok().map(|damage| damage.0))
+            .into_iter()
+            .collect(),
+        None => damage_dice(ecs, attacker),
+    }
+}
+
 #[system]
 #[read_component(WantsToAttack)]
+#[read_component(RangedAttack)]
+#[read_component(Ammo)]
+#[read_component(Equippable)]
 #[read_component(Player)]
 #[write_component(Health)]
 #[read_component(Carried)]
 #[read_component(Damage)]
-pub fn combat(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
-    let mut attackers = <(Entity, &WantsToAttack)>::query();
+#[read_component(Armor)]
+#[read_component(Accuracy)]
+#[read_component(Evasion)]
+#[read_component(Equipped)]
+#[read_component(Name)]
+#[read_component(InflictsStatus)]
+#[read_component(StatusEffects)]
+pub fn combat(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] log: &mut MessageLog,
+    #[resource] rng: &mut RandomNumberGenerator,
+) {
+    let mut attackers = <(Entity, &WantsToAttack, Option<&RangedAttack>)>::query();
 
-    let victims: Vec<(_, _, _)> = attackers
+    let victims: Vec<(_, _, _, _)> = attackers
         .iter(ecs)
-        .map(|(entity, attack)| (*entity, attack.attacker, attack.victim))
+        .map(|(entity, attack, ranged)| {
+            let weapon = ranged.map(|ranged| ranged.weapon);
+            (*entity, attack.attacker, attack.victim, weapon)
+        })
         .collect();
 
-    victims.iter().for_each(|(message, attacker, victim)| {
-        let is_player = ecs
-            .entry_ref(*victim)
-            .unwrap()
-            .get_component::<Player>()
-            .is_ok();
+    victims
+        .iter()
+        .for_each(|(message, attacker, victim, ranged_weapon)| {
+            let is_player = ecs
+                .entry_ref(*victim)
+                .unwrap()
+                .get_component::<Player>()
+                .is_ok();
 
-        let base_damage = if let Ok(attacker) = ecs.entry_ref(*attacker) {
-            if let Ok(dmg) = attacker.get_component::<Damage>() {
-                dmg.0
+            let roll = AttackRoll::roll(
+                rng,
+                accuracy(ecs, *attacker),
+                evasion(ecs, *victim),
+                &attack_dice(ecs, *attacker, *ranged_weapon),
+                defence(ecs, *victim),
+            );
+            let final_damage = roll.damage();
+
+            let attacker_name = display_name(ecs, *attacker);
+            let victim_name = display_name(ecs, *victim);
+            let category = if is_player {
+                MessageCategory::Danger
             } else {
-                0
+                MessageCategory::Combat
+            };
+
+            if let Ok(health) = ecs
+                .entry_mut(*victim)
+                .unwrap()
+                .get_component_mut::<Health>()
+            {
+                health.current -= final_damage;
+                let text = if !roll.hit() {
+                    format!("{} {}.", subject_verb(&attacker_name, "miss"), victim_name)
+                } else if final_damage == 0 {
+                    format!(
+                        "{} {}, but the blow glances off.",
+                        subject_verb(&attacker_name, "hit"),
+                        victim_name
+                    )
+                } else if roll.is_critical() {
+                    format!(
+                        "{} {} for {}!",
+                        subject_verb(&attacker_name, "land a critical hit on"),
+                        victim_name,
+                        final_damage
+                    )
+                } else {
+                    format!(
+                        "{} {} for {}.",
+                        subject_verb(&attacker_name, "hit"),
+                        victim_name,
+                        final_damage
+                    )
+                };
+                log.add(category, format!("{} [{}]", text, roll.breakdown()));
+
+                if health.current < 1 && !is_player {
+                    commands.remove(*victim);
+                    log.add(category, format!("{} dies.", capitalize(&victim_name)));
+                } else if final_damage > 0 {
+                    let inflicts = ecs
+                        .entry_ref(*attacker)
+                        .ok()
+                        .and_then(|entry| entry.get_component::<InflictsStatus>().ok().cloned());
+                    if let Some(inflicts) = inflicts {
+                        apply_status(ecs, commands, log, *victim, &inflicts.0);
+                    }
+                }
             }
-        } else {
-            0
-        };
+            if let Some(weapon) = ranged_weapon {
+                use_ammo(ecs, commands, log, *weapon);
+            }
+            commands.remove(*message)
+        });
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
 
-        let weapon_damage: i32 = <(&Carried, &Damage)>::query()
-            .iter(ecs)
-            .filter(|(&carried, _)| carried.0 == *attacker)
-            .map(|(_, dmg)| dmg.0)
-            .sum();
+    fn flat(amount: i32) -> Damage {
+        Damage(Dice::flat(amount))
+    }
 
-        let final_damage = base_damage + weapon_damage;
+    /// Gives `entity` enough accuracy that only the seed decides whether it crits.
+    fn never_miss(world: &mut World, entity: Entity) {
+        world.entry(entity).unwrap().add_component(Accuracy(100));
+    }
 
-        if let Ok(health) = ecs
-            .entry_mut(*victim)
+    fn fight(world: &mut World, attacks: &[(Entity, Entity)]) -> MessageLog {
+        let mut resources = Resources::default();
+        resources.insert(MessageLog::default());
+        resources.insert(RandomNumberGenerator::seeded(2));
+        for &(attacker, victim) in attacks {
+            world.push(((), WantsToAttack { attacker, victim }));
+        }
+
+        let mut schedule = Schedule::builder().add_system(combat_system()).build();
+        schedule.execute(world, &mut resources);
+
+        let log = resources.get::<MessageLog>().unwrap().clone();
+        log
+    }
+
+    fn health(world: &World, entity: Entity) -> i32 {
+        world
+            .entry_ref(entity)
+            .unwrap()
+            .get_component::<Health>()
             .unwrap()
-            .get_component_mut::<Health>()
-        {
-            println!("Health before attack: {}", health.current);
-            health.current -= final_damage;
-            if health.current < 1 && !is_player {
-                commands.remove(*victim);
+            .current
+    }
+
+    #[test]
+    fn test_combat_is_logged() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        let orc = world.push((
+            Enemy,
+            Name("Orc".to_string()),
+            Health::new(2),
+            flat(1),
+            Point::new(1, 0),
+        ));
+        never_miss(&mut world, player);
+        never_miss(&mut world, orc);
+
+        let log = fight(&mut world, &[(orc, player), (player, orc)]);
+
+        let messages: Vec<(String, MessageCategory)> = log
+            .visible(10)
+            .iter()
+            .map(|message| (message.text.clone(), message.category))
+            .collect();
+        assert!(messages.iter().any(|(text, category)| text
+            .starts_with("The Orc hits you for 1. [d20 ")
+            && *category == MessageCategory::Danger));
+        assert!(messages.iter().any(|(text, category)| text
+            .starts_with("You hit the Orc for 1. [d20 ")
+            && *category == MessageCategory::Combat));
+    }
+
+    #[test]
+    fn test_only_equipped_gear_adds_damage() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        let sword = Equippable(EquipmentSlot::MainHand);
+        world.push((
+            Item,
+            Carried(player),
+            flat(2),
+            sword,
+            Equipped(EquipmentSlot::MainHand),
+        ));
+        world.push((Item, Carried(player), flat(5), sword));
+        let ogre = world.push((Enemy, Health::new(10), Point::new(1, 0)));
+        never_miss(&mut world, player);
+
+        fight(&mut world, &[(player, ogre)]);
+
+        assert_eq!(health(&world, ogre), 7);
+    }
+
+    #[test]
+    fn test_armor_mitigates_damage() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        world.push((
+            Item,
+            Carried(player),
+            Armor(1),
+            Equipped(EquipmentSlot::Body),
+        ));
+        let ettin = world.push((
+            Enemy,
+            Name("Ettin".to_string()),
+            Health::new(10),
+            flat(3),
+            Armor(2),
+        ));
+        never_miss(&mut world, player);
+        never_miss(&mut world, ettin);
+
+        let log = fight(&mut world, &[(ettin, player), (player, ettin)]);
+
+        assert_eq!(health(&world, player), 8);
+        assert_eq!(health(&world, ettin), 10);
+        assert!(log.visible(1)[0]
+            .text
+            .starts_with("You hit the Ettin, but the blow glances off."));
+    }
+
+    #[test]
+    fn test_attack_rolls_follow_the_seed() {
+        let dice = ["1d6+1".parse().unwrap(), Dice::flat(1)];
+        let rolls = |seed| {
+            let mut rng = RandomNumberGenerator::seeded(seed);
+            (0..50)
+                .map(|_| AttackRoll::roll(&mut rng, 2, 1, &dice, 1))
+                .collect::<Vec<_>>()
+        };
+
+        let rolls_one = rolls(1);
+        assert_eq!(rolls_one, rolls(1));
+        assert_ne!(rolls_one, rolls(2));
+
+        for roll in rolls_one.iter() {
+            assert_eq!(roll.target, TO_HIT + 1);
+            assert_eq!(roll.hit(), roll.d20 == 20 || roll.d20 + 2 >= roll.target);
+            let max_damage = if roll.is_critical() { 14 } else { 8 };
+            if roll.hit() {
+                assert!((2..=max_damage).contains(&roll.damage()));
+            } else {
+                assert_eq!(roll.damage(), 0);
             }
-            println!("Health after attack: {}", health.current);
         }
-        commands.remove(*message)
-    });
+        assert!(rolls_one.iter().any(|roll| !roll.hit()));
+    }
+
+    #[test]
+    fn test_criticals_always_hit_and_double_the_dice() {
+        let roll = AttackRoll {
+            d20: 20,
+            accuracy: 0,
+            target: 30,
+            damage: vec![("2d4".parse().unwrap(), 13)],
+            defence: 1,
+        };
+
+        assert!(roll.hit());
+        assert_eq!(roll.damage(), 12);
+        assert_eq!(
+            roll.breakdown(),
+            "d20 20+0 vs 30 (critical); 2d4 = 13; -1 armor"
+        );
+    }
+
+    #[test]
+    fn test_hits_inflict_status_effects() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        let poison = StatusEffect::new(StatusKind::Poison, 3);
+        let rat = world.push((
+            Enemy,
+            Name("Giant Rat".to_string()),
+            Health::new(1),
+            flat(1),
+            InflictsStatus(vec![poison]),
+        ));
+        never_miss(&mut world, rat);
+
+        let log = fight(&mut world, &[(rat, player)]);
+
+        let effects = world
+            .entry_ref(player)
+            .unwrap()
+            .get_component::<StatusEffects>()
+            .unwrap()
+            .clone();
+        assert_eq!(effects, StatusEffects(vec![poison]));
+        assert_eq!(log.visible(1)[0].text, "You are poisoned.");
+    }
+
+    #[test]
+    fn test_shots_use_the_weapon_and_its_ammo() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        never_miss(&mut world, player);
+        let knife = world.push((
+            Item,
+            Name("Throwing Knives".to_string()),
+            Carried(player),
+            flat(3),
+            Ranged { range: 4 },
+            Ammo(1),
+        ));
+        let orc = world.push((Enemy, Name("Orc".to_string()), Health::new(10)));
+        world.push((
+            (),
+            WantsToAttack {
+                attacker: player,
+                victim: orc,
+            },
+            RangedAttack { weapon: knife },
+        ));
+
+        let log = fight(&mut world, &[]);
+
+        assert_eq!(health(&world, orc), 7);
+        assert!(world.entry_ref(knife).is_err());
+        assert_eq!(
+            log.visible(1)[0].text,
+            "That was the last of the Throwing Knives."
+        );
+    }
 }
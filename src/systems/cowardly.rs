@@ -0,0 +1,93 @@
+use super::chasing::{attack_or_move, step_towards};
+use super::fleeing::is_fleeing;
+use crate::prelude::*;
+
+/// Cowards go for a player they can see, but unlike chasers they don't remember them once they're
+/// out of sight. They spawn with a `FleesBelow`, so `fleeing` takes over when they're hurt.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Health)]
+#[read_component(Cowardly)]
+#[read_component(FleesBelow)]
+#[read_component(FieldOfView)]
+pub fn cowardly(
+    ecs: &SubWorld,
+    #[resource] map: &Map,
+    #[resource] flow: &FlowField,
+    #[resource] index: &SpatialIndex,
+    commands: &mut CommandBuffer,
+) {
+    let player_pos = *<&Point>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+        .unwrap();
+    let djikstra_map = match flow.distances() {
+        Some(distances) => distances,
+        None => return,
+    };
+
+    for (entity, pos, fov) in <(Entity, &Point, &FieldOfView)>::query()
+        .filter(component::<Cowardly>())
+        .iter(ecs)
+    {
+        if !fov.visible_tiles.contains(&player_pos) || is_fleeing(ecs, *entity) {
+            continue;
+        }
+        if let Some(destination) = step_towards(map, djikstra_map, *pos, player_pos) {
+            attack_or_move(ecs, index, commands, *entity, destination);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::systems::{flow_field_system, spatial_index_system};
+    use empty::EmptyArchitect;
+
+    fn coward_step(hp: i32) -> Option<Point> {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let map = EmptyArchitect {}
+            .build(MAP_WIDTH, MAP_HEIGHT, &mut RandomNumberGenerator::seeded(1))
+            .map;
+        resources.insert(map);
+        resources.insert(FlowField::default());
+        resources.insert(SpatialIndex::default());
+        spawn_player(&mut world, Point::new(5, 5));
+        let mut fov = FieldOfView::new(6);
+        fov.visible_tiles.insert(Point::new(5, 5));
+        let health = Health {
+            current: hp,
+            max: 4,
+        };
+        world.push((
+            Enemy,
+            Cowardly,
+            FleesBelow(3),
+            fov,
+            health,
+            Point::new(8, 5),
+        ));
+
+        let mut schedule = Schedule::builder()
+            .add_system(spatial_index_system())
+            .add_system(flow_field_system())
+            .add_system(cowardly_system())
+            .build();
+        schedule.execute(&mut world, &mut resources);
+
+        <&WantsToMove>::query()
+            .iter(&world)
+            .next()
+            .map(|want_move| want_move.destination)
+    }
+
+    #[test]
+    fn test_cowards_chase_while_healthy_and_leave_fleeing_to_fleeing() {
+        assert_eq!(coward_step(4), Some(Point::new(7, 5)));
+        assert_eq!(coward_step(2), None);
+    }
+}
@@ -0,0 +1,138 @@
+use crate::prelude::*;
+
+#[system(for_each)]
+#[read_component(FieldOfView)]
+pub fn doors(
+    entity: &Entity,
+    want_close: &WantsToCloseDoor,
+    #[resource] map: &mut Map,
+    #[resource] index: &SpatialIndex,
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+) {
+    // Only creatures hold a door open; items lying in the doorway get shut in.
+    let blocked = index.creature_at(want_close.position).is_some();
+
+    if map.try_tile(want_close.position) == Some(TileType::DoorOpen) && !blocked {
+        let idx = map.point2d_to_index(want_close.position);
+        map.tiles[idx] = TileType::DoorClosed;
+        dirty_all_fovs(ecs, commands);
+    }
+
+    commands.remove(*entity);
+}
+
+/// Opening or closing a door changes what everyone can see, not just whoever used it.
+pub fn dirty_all_fovs(ecs: &SubWorld, commands: &mut CommandBuffer) {
+    for (entity, fov) in <(Entity, &FieldOfView)>::query().iter(ecs) {
+        commands.add_component(*entity, fov.clone_dirty());
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::systems::{fov_system, spatial_index_system};
+    use empty::EmptyArchitect;
+
+    struct DoorSystemTest {
+        world: World,
+        resources: Resources,
+        schedule: Schedule,
+        player: Entity,
+        door: Point,
+    }
+
+    impl DoorSystemTest {
+        fn new() -> Self {
+            let mut world = World::default();
+            let mut resources = Resources::default();
+            let mut map = EmptyArchitect {}
+                .build(MAP_WIDTH, MAP_HEIGHT, &mut RandomNumberGenerator::new())
+                .map;
+            let door = Point::new(1, 0);
+            let idx = map.point2d_to_index(door);
+            map.tiles[idx] = TileType::DoorOpen;
+            resources.insert(map);
+            resources.insert(SpatialIndex::default());
+
+            let player = spawn_player(&mut world, Point::zero());
+            let schedule = Schedule::builder()
+                .add_system(spatial_index_system())
+                .add_system(doors_system())
+                .flush()
+                .add_system(fov_system())
+                .build();
+
+            Self {
+                world,
+                resources,
+                schedule,
+                player,
+                door,
+            }
+        }
+
+        fn close_door(&mut self) {
+            self.world.push((
+                (),
+                WantsToCloseDoor {
+                    entity: self.player,
+                    position: self.door,
+                },
+            ));
+            self.schedule.execute(&mut self.world, &mut self.resources);
+        }
+
+        fn door_tile(&self) -> TileType {
+            let map = self.resources.get::<Map>().unwrap();
+            map.tiles[map.point2d_to_index(self.door)]
+        }
+
+        fn player_can_see(&self, pt: Point) -> bool {
+            self.world
+                .entry_ref(self.player)
+                .unwrap()
+                .get_component::<FieldOfView>()
+                .unwrap()
+                .visible_tiles
+                .contains(&pt)
+        }
+    }
+
+    #[test]
+    fn test_closing_door_blocks_sight() {
+        let mut state = DoorSystemTest::new();
+        state
+            .schedule
+            .execute(&mut state.world, &mut state.resources);
+        assert!(state.player_can_see(Point::new(3, 0)));
+
+        state.close_door();
+
+        assert_eq!(state.door_tile(), TileType::DoorClosed);
+        assert!(state.player_can_see(state.door));
+        assert!(!state.player_can_see(Point::new(3, 0)));
+        assert_eq!(<&WantsToCloseDoor>::query().iter(&state.world).count(), 0);
+    }
+
+    #[test]
+    fn test_cannot_close_occupied_door() {
+        let mut state = DoorSystemTest::new();
+        state.world.push((Enemy, state.door, Health::new(1)));
+
+        state.close_door();
+
+        assert_eq!(state.door_tile(), TileType::DoorOpen);
+    }
+
+    #[test]
+    fn test_items_do_not_hold_door_open() {
+        let mut state = DoorSystemTest::new();
+        state.world.push((Item, state.door));
+
+        state.close_door();
+
+        assert_eq!(state.door_tile(), TileType::DoorClosed);
+    }
+}
@@ -10,12 +10,13 @@ pub fn end_turn(ecs: &SubWorld, #[resource] turn_state: &mut TurnState, #[resour
     let mut amulet = <&Point>::query().filter(component::<AmuletOfYala>());
 
     let mut new_state = match turn_state {
-        TurnState::AwaitingInput => return,
+        TurnState::MainMenu | TurnState::AwaitingInput => return,
         TurnState::PlayerTurn => TurnState::MonsterTurn,
         TurnState::MonsterTurn => TurnState::AwaitingInput,
         TurnState::GameOver => *turn_state,
         TurnState::Victory => *turn_state,
         TurnState::NextLevel => *turn_state,
+        TurnState::PreviousLevel => *turn_state,
     };
 
     let amulet_pos = amulet.iter(ecs).next();
@@ -29,8 +30,10 @@ pub fn end_turn(ecs: &SubWorld, #[resource] turn_state: &mut TurnState, #[resour
                 new_state = TurnState::Victory;
             }
         }
-        if let Some(TileType::Exit) = map.try_tile(*pos) {
-            new_state = TurnState::NextLevel;
+        match map.try_tile(*pos) {
+            Some(TileType::Exit) => new_state = TurnState::NextLevel,
+            Some(TileType::UpStairs) => new_state = TurnState::PreviousLevel,
+            _ => {}
         }
     }
 
@@ -7,7 +7,11 @@ use crate::prelude::*;
 #[read_component(Render)]
 #[read_component(FieldOfView)]
 #[read_component(Player)]
-pub fn entity_render(ecs: &SubWorld, #[resource] camera: &Camera) {
+pub fn entity_render(
+    ecs: &SubWorld,
+    #[resource] camera: &Camera,
+    #[resource] index: &SpatialIndex,
+) {
     let mut fov = <&FieldOfView>::query().filter(component::<Player>());
     let player_fov = fov.iter(ecs).next().unwrap();
 
@@ -16,12 +20,24 @@ pub fn entity_render(ecs: &SubWorld, #[resource] camera: &Camera) {
 
     let offset = camera.offset();
 
-    <(&Point, &Render)>::query()
-        .iter(ecs)
-        .filter(|(pos, _)| player_fov.visible_tiles.contains(*pos))
-        .for_each(|(pos, render)| {
-            draw_batch.set(*pos - offset, render.color, render.glyph);
-        });
+    for pos in player_fov.visible_tiles.iter() {
+        // Whoever is standing on a tile is drawn over anything lying there.
+        let creature = index.creature_at(*pos);
+        let entities = index
+            .entities_at(*pos)
+            .iter()
+            .filter(|entity| Some(**entity) != creature)
+            .chain(creature.iter());
+        for entity in entities {
+            let render = ecs
+                .entry_ref(*entity)
+                .ok()
+                .and_then(|entry| entry.get_component::<Render>().ok().copied());
+            if let Some(render) = render {
+                draw_batch.set(*pos - offset, render.color, render.glyph);
+            }
+        }
+    }
 
     draw_batch.submit(5000).expect("Entity Draw Batch Error");
 }
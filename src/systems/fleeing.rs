@@ -0,0 +1,125 @@
+use crate::prelude::*;
+
+/// Monsters whose health has dropped below their `FleesBelow` run from a player they can see,
+/// following the safety map downhill. The other AI systems leave them alone meanwhile.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Health)]
+#[read_component(FleesBelow)]
+#[read_component(FieldOfView)]
+pub fn fleeing(
+    ecs: &SubWorld,
+    #[resource] map: &Map,
+    #[resource] flow: &mut FlowField,
+    commands: &mut CommandBuffer,
+) {
+    let player_pos = *<&Point>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+        .unwrap();
+    let fleeing: Vec<(Entity, Point)> = <(Entity, &Point, &FieldOfView)>::query()
+        .filter(component::<FleesBelow>())
+        .iter(ecs)
+        .filter(|(entity, _, fov)| {
+            is_fleeing(ecs, **entity) && fov.visible_tiles.contains(&player_pos)
+        })
+        .map(|(entity, pos, _)| (*entity, *pos))
+        .collect();
+    if fleeing.is_empty() {
+        return;
+    }
+
+    let safety = match flow.safety(map) {
+        Some(safety) => safety,
+        None => return,
+    };
+    for (entity, pos) in fleeing {
+        let idx = map.idx(pos.x, pos.y);
+        let exit = DijkstraMap::find_lowest_exit(safety, idx, map)
+            .filter(|exit| safety.map[*exit] < safety.map[idx]);
+        if let Some(exit) = exit {
+            commands.push((
+                (),
+                WantsToMove {
+                    entity,
+                    destination: map.index_to_point2d(exit),
+                },
+            ));
+        }
+    }
+}
+
+/// Whether `entity` is hurt badly enough to run. The caller has to be able to read `Health` and
+/// `FleesBelow`.
+pub(super) fn is_fleeing(ecs: &SubWorld, entity: Entity) -> bool {
+    ecs.entry_ref(entity).is_ok_and(|entry| {
+        match (
+            entry.get_component::<Health>(),
+            entry.get_component::<FleesBelow>(),
+        ) {
+            (Ok(health), Ok(flees_below)) => health.current < flees_below.0,
+            _ => false,
+        }
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::systems::flow_field_system;
+
+    /// A corridor running east from the player past the monster, then south to a big room, with
+    /// a dead end leading north from the monster. Both ways out are the same distance from the
+    /// player for the first step, and ties go north.
+    fn dead_end_map() -> Map {
+        let mut map = Map::new(MAP_WIDTH, MAP_HEIGHT);
+        map.tiles.iter_mut().for_each(|tile| *tile = TileType::Wall);
+        let mut carve = |x: i32, y: i32| {
+            let idx = map.idx(x, y);
+            map.tiles[idx] = TileType::Floor;
+        };
+        (5..=12).for_each(|x| carve(x, 5));
+        (3..=4).for_each(|y| carve(7, y));
+        (6..=15).for_each(|y| carve(12, y));
+        for y in 16..=30 {
+            (3..=30).for_each(|x| carve(x, y));
+        }
+        map
+    }
+
+    fn flee_step(health: Health) -> Option<Point> {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(dead_end_map());
+        resources.insert(FlowField::default());
+        spawn_player(&mut world, Point::new(5, 5));
+        let mut fov = FieldOfView::new(6);
+        fov.visible_tiles.insert(Point::new(5, 5));
+        world.push((Enemy, FleesBelow(3), health, fov, Point::new(7, 5)));
+
+        let mut schedule = Schedule::builder()
+            .add_system(flow_field_system())
+            .add_system(fleeing_system())
+            .build();
+        schedule.execute(&mut world, &mut resources);
+
+        <&WantsToMove>::query()
+            .iter(&world)
+            .next()
+            .map(|want_move| want_move.destination)
+    }
+
+    #[test]
+    fn test_fleeing_avoids_dead_ends() {
+        let wounded = Health { current: 2, max: 4 };
+
+        assert_eq!(flee_step(wounded), Some(Point::new(8, 5)));
+    }
+
+    #[test]
+    fn test_only_monsters_below_the_threshold_flee() {
+        assert_eq!(flee_step(Health { current: 3, max: 4 }), None);
+    }
+}
@@ -0,0 +1,15 @@
+use crate::prelude::*;
+
+/// Brings the shared `FlowField` up to date before any monster decides where to go.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+pub fn flow_field(ecs: &SubWorld, #[resource] map: &Map, #[resource] flow: &mut FlowField) {
+    if let Some(player_pos) = <&Point>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+    {
+        flow.update(map, *player_pos);
+    }
+}
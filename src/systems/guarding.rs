@@ -0,0 +1,62 @@
+use super::chasing::is_adjacent;
+use super::fleeing::is_fleeing;
+use crate::prelude::*;
+
+/// Guards never leave their post, but hit the player whenever they're next to them.
+#[system]
+#[read_component(Point)]
+#[read_component(Player)]
+#[read_component(Guarding)]
+#[read_component(Health)]
+#[read_component(FleesBelow)]
+pub fn guarding(ecs: &SubWorld, commands: &mut CommandBuffer) {
+    let (player, player_pos) = <(Entity, &Point)>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .map(|(entity, pos)| (*entity, *pos))
+        .next()
+        .unwrap();
+
+    for (entity, _) in <(Entity, &Point)>::query()
+        .filter(component::<Guarding>())
+        .iter(ecs)
+        .filter(|(entity, pos)| is_adjacent(**pos, player_pos) && !is_fleeing(ecs, **entity))
+    {
+        commands.push((
+            (),
+            WantsToAttack {
+                attacker: *entity,
+                victim: player,
+            },
+        ));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_guards_only_attack_when_the_player_is_adjacent() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let player = spawn_player(&mut world, Point::new(5, 5));
+        let guard = world.push((Enemy, Guarding, Point::new(8, 5)));
+        let mut schedule = Schedule::builder().add_system(guarding_system()).build();
+
+        schedule.execute(&mut world, &mut resources);
+        assert_eq!(<&WantsToMove>::query().iter(&world).count(), 0);
+        assert_eq!(<&WantsToAttack>::query().iter(&world).count(), 0);
+
+        world.entry(guard).unwrap().add_component(Point::new(6, 5));
+        schedule.execute(&mut world, &mut resources);
+        let attacks: Vec<WantsToAttack> = <&WantsToAttack>::query().iter(&world).copied().collect();
+        assert_eq!(
+            attacks,
+            vec![WantsToAttack {
+                attacker: guard,
+                victim: player
+            }]
+        );
+    }
+}
@@ -1,12 +1,19 @@
 use crate::prelude::*;
 
+const LOG_LINES: usize = 6;
+/// Items beyond these are only reachable through the inventory screen.
+const QUICK_SLOTS: usize = 9;
+
 #[system]
 #[read_component(Player)]
 #[read_component(Health)]
 #[read_component(Item)]
 #[read_component(Carried)]
 #[read_component(Name)]
-pub fn hud(ecs: &SubWorld) {
+#[read_component(Equipped)]
+#[read_component(Armor)]
+#[read_component(StatusEffects)]
+pub fn hud(ecs: &SubWorld, #[resource] seed: &RunSeed, #[resource] log: &MessageLog) {
     let mut health_query = <&Health>::query().filter(component::<Player>());
 
     let player_health = health_query.iter(ecs).next().unwrap();
@@ -15,7 +22,10 @@ pub fn hud(ecs: &SubWorld) {
 
     draw_batch.target(LAYER_HUD);
 
-    draw_batch.print_centered(1, "Explore the Dungeon. Cursor keys to move.");
+    draw_batch.print_centered(
+        1,
+        "Explore the Dungeon. Cursor keys to move, C to close doors, F to fire, I for inventory.",
+    );
     draw_batch.bar_horizontal(
         Point::zero(),
         SCREEN_WIDTH * 2,
@@ -36,18 +46,19 @@ pub fn hud(ecs: &SubWorld) {
         .next()
         .unwrap();
 
-    let mut item_query = <(&Item, &Name, &Carried)>::query();
-
-    let mut y = 3;
-    for (_, name, _) in item_query
-        .iter(ecs)
-        .filter(|(_, _, &Carried(carried_by))| carried_by == player)
-    {
-        draw_batch.print(Point::new(3, y), format!("{} : {}", y - 2, &name.0));
-        y += 1;
+    let items = carried_items(ecs, player);
+    for ((_, name), y) in items.iter().take(QUICK_SLOTS).zip(3..) {
+        draw_batch.print(Point::new(3, y), format!("{} : {}", y - 2, name));
+    }
+    if items.len() > QUICK_SLOTS {
+        draw_batch.print_color(
+            Point::new(3, 3 + QUICK_SLOTS as i32),
+            format!("+{} more (I: inventory)", items.len() - QUICK_SLOTS),
+            ColorPair::new(GRAY, BLACK),
+        );
     }
 
-    if y > 3 {
+    if !items.is_empty() {
         draw_batch.print_color(
             Point::new(3, 2),
             "Items Carried",
@@ -61,5 +72,51 @@ pub fn hud(ecs: &SubWorld) {
         ColorPair::new(YELLOW, BLACK),
     );
 
+    draw_batch.print_color_right(
+        Point::new(SCREEN_WIDTH * 2, 2),
+        format!("Seed {}", seed.0),
+        ColorPair::new(GRAY, BLACK),
+    );
+
+    draw_batch.print_color_right(
+        Point::new(SCREEN_WIDTH * 2, 3),
+        format!("Defence {}", defence(ecs, player)),
+        ColorPair::new(CYAN, BLACK),
+    );
+
+    if let Ok(effects) = ecs
+        .entry_ref(player)
+        .unwrap()
+        .get_component::<StatusEffects>()
+    {
+        let mut x = SCREEN_WIDTH * 2;
+        for effect in effects.0.iter().rev() {
+            let (icon, color) = effect.kind.icon();
+            let text = format!("{}{} ", icon, effect.turns);
+            x -= text.chars().count() as i32;
+            draw_batch.print_color(Point::new(x, 4), text, ColorPair::new(color, BLACK));
+        }
+    }
+
+    // Kept clear of the bottom two rows, where the replay status goes.
+    let log_top = SCREEN_HEIGHT * 2 - 3 - LOG_LINES as i32;
+    let heading = if log.is_scrolled() {
+        "Messages (scrolled back, PgDn for newer)"
+    } else {
+        "Messages (PgUp to scroll back)"
+    };
+    draw_batch.print_color(
+        Point::new(1, log_top - 1),
+        heading,
+        ColorPair::new(GRAY, BLACK),
+    );
+    for (message, y) in log.visible(LOG_LINES).iter().zip(log_top..) {
+        draw_batch.print_color(
+            Point::new(1, y),
+            &message.text,
+            ColorPair::new(message.category.color(), BLACK),
+        );
+    }
+
     draw_batch.submit(10000).expect("HUD draw batch error");
 }
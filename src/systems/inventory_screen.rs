@@ -0,0 +1,93 @@
+use crate::prelude::*;
+
+#[system]
+#[read_component(Player)]
+#[read_component(Item)]
+#[read_component(Carried)]
+#[read_component(Name)]
+#[read_component(AreaOfEffect)]
+#[read_component(Provides)]
+#[read_component(Damage)]
+#[read_component(Armor)]
+#[read_component(Ranged)]
+#[read_component(Ammo)]
+#[read_component(Accuracy)]
+#[read_component(Evasion)]
+#[read_component(Equippable)]
+#[read_component(Equipped)]
+pub fn inventory_screen(ecs: &SubWorld, #[resource] screen: &InventoryScreen) {
+    let mode = match screen.mode {
+        Some(mode) => mode,
+        None => return,
+    };
+
+    let player = *<Entity>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+        .unwrap();
+    let items = carried_items(ecs, player);
+
+    let frame = Rect::with_size(20, 10, SCREEN_WIDTH * 2 - 41, ITEMS_PER_PAGE as i32 + 7);
+    let mut draw_batch = DrawBatch::new();
+    draw_batch.target(LAYER_HUD);
+    draw_batch.draw_double_box(frame, ColorPair::new(WHITE, BLACK));
+    let left = frame.x1 + 2;
+
+    if let Some(item) = screen.examining {
+        let name = items
+            .iter()
+            .find(|(entity, _)| *entity == item)
+            .map(|(_, name)| name.as_str())
+            .unwrap_or_default();
+        draw_batch.print_color(
+            Point::new(left, frame.y1 + 1),
+            name,
+            ColorPair::new(YELLOW, BLACK),
+        );
+        for (line, y) in describe_item(ecs, item).iter().zip(frame.y1 + 3..) {
+            draw_batch.print(Point::new(left, y), line);
+        }
+        draw_batch.print_color(
+            Point::new(left, frame.y2 - 1),
+            "Escape: back to the list",
+            ColorPair::new(GRAY, BLACK),
+        );
+    } else {
+        draw_batch.print_color(
+            Point::new(left, frame.y1 + 1),
+            mode.title(),
+            ColorPair::new(YELLOW, BLACK),
+        );
+
+        if items.is_empty() {
+            draw_batch.print(
+                Point::new(left, frame.y1 + 3),
+                "You aren't carrying anything.",
+            );
+        }
+        for ((letter, (_, name)), y) in screen.page_items(&items).zip(frame.y1 + 3..) {
+            draw_batch.print(Point::new(left, y), format!("{}) {}", letter, name));
+        }
+
+        let pages = items.len().div_ceil(ITEMS_PER_PAGE);
+        let footer = if pages > 1 {
+            format!(
+                "Page {}/{} - PgUp/PgDn: page, Tab: use/drop/examine, Escape: close",
+                screen.page + 1,
+                pages
+            )
+        } else {
+            "Tab: use/drop/examine, Escape: close".to_string()
+        };
+        draw_batch.print_color(
+            Point::new(left, frame.y2 - 1),
+            footer,
+            ColorPair::new(GRAY, BLACK),
+        );
+    }
+
+    draw_batch
+        .submit(10200)
+        .expect("Inventory draw batch error");
+}
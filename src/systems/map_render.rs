@@ -19,7 +19,7 @@ pub fn map_render(
 
     for y in camera.top_y..=camera.bottom_y {
         for x in camera.left_x..camera.right_x {
-            let idx = map_idx(x, y);
+            let idx = map.idx(x, y);
             let pt = Point::new(x, y);
             let offset = camera.offset();
 
@@ -11,43 +11,59 @@ dry_mods::mods! {
     mod use tooltips;
     mod use combat;
     mod use chasing;
+    mod use guarding;
+    mod use cowardly;
+    mod use ambushing;
+    mod use fleeing;
+    mod use flow_field;
+    mod use spatial_index;
     mod use fov;
     mod use use_items;
+    mod use doors;
+    mod use inventory_screen;
+    mod use status_effects;
+    mod use targeting_render;
 }
 
 pub fn build_input_scheduler() -> Schedule {
     Schedule::builder()
+        .add_system(spatial_index_system())
+        .flush()
         .add_system(player_input::player_input_system())
         .flush()
         .add_system(fov_system())
-        .flush()
-        .add_system(map_render_system())
-        .add_system(entity_render_system())
-        .add_system(hud_system())
-        .add_system(tooltips_system())
         .build()
 }
 
 pub fn build_player_scheduler() -> Schedule {
     Schedule::builder()
+        .add_system(spatial_index_system())
+        .add_system(status_effects_system())
+        .flush()
         .add_system(use_items_system())
         .add_system(combat_system())
+        .add_system(doors_system())
         .flush()
         .add_system(movement_system())
         .flush()
         .add_system(fov_system())
         .flush()
-        .add_system(map_render_system())
-        .add_system(entity_render_system())
-        .add_system(hud_system())
         .add_system(end_turn_system())
         .build()
 }
 
 pub fn build_monster_scheduler() -> Schedule {
     Schedule::builder()
+        .add_system(spatial_index_system())
+        .add_system(flow_field_system())
         .add_system(random_move_system())
         .add_system(chasing_system())
+        .add_system(guarding_system())
+        .add_system(cowardly_system())
+        .add_system(ambushing_system())
+        .add_system(fleeing_system())
+        .flush()
+        .add_system(status_effects_system())
         .flush()
         .add_system(use_items_system())
         .add_system(combat_system())
@@ -56,9 +72,18 @@ pub fn build_monster_scheduler() -> Schedule {
         .flush()
         .add_system(fov_system())
         .flush()
+        .add_system(end_turn_system())
+        .build()
+}
+
+/// Drawing is kept out of the turn schedules so the game can be stepped without a window.
+pub fn build_render_scheduler() -> Schedule {
+    Schedule::builder()
         .add_system(map_render_system())
         .add_system(entity_render_system())
         .add_system(hud_system())
-        .add_system(end_turn_system())
+        .add_system(tooltips_system())
+        .add_system(targeting_render_system())
+        .add_system(inventory_screen_system())
         .build()
 }
@@ -1,3 +1,4 @@
+use super::doors::dirty_all_fovs;
 use crate::prelude::*;
 
 #[system(for_each)]
@@ -6,32 +7,49 @@ use crate::prelude::*;
 // Ask me how I know 😅
 #[read_component(Player)]
 #[read_component(FieldOfView)]
+#[read_component(Point)]
 pub fn movement(
     entity: &Entity,
     want_move: &WantsToMove,
     #[resource] map: &mut Map,
     #[resource] camera: &mut Camera,
+    #[resource] index: &mut SpatialIndex,
     ecs: &mut SubWorld,
     commands: &mut CommandBuffer,
 ) {
-    if map.can_enter_tile(want_move.destination) {
+    // Someone else is standing there, or got there first this turn. Creatures killed since the
+    // index was rebuilt are already gone from the world.
+    let occupied = index
+        .creature_at(want_move.destination)
+        .is_some_and(|creature| creature != want_move.entity && ecs.entry_ref(creature).is_ok());
+
+    if map.try_tile(want_move.destination) == Some(TileType::DoorClosed) {
+        let idx = map.point2d_to_index(want_move.destination);
+        map.tiles[idx] = TileType::DoorOpen;
+        dirty_all_fovs(ecs, commands);
+    } else if !occupied && map.can_enter_tile(want_move.destination) {
         commands.add_component(want_move.entity, want_move.destination);
 
         if let Ok(entry) = ecs.entry_ref(want_move.entity) {
+            if let Ok(pos) = entry.get_component::<Point>() {
+                index.move_entity(want_move.entity, *pos, want_move.destination);
+            }
             if let Ok(fov) = entry.get_component::<FieldOfView>() {
                 commands.add_component(want_move.entity, fov.clone_dirty());
 
                 if entry.get_component::<Player>().is_ok() {
                     camera.on_player_move(want_move.destination);
                     for pos in fov.visible_tiles.iter() {
-                        map.revealed_tiles[map_idx(pos.x, pos.y)] = true;
+                        let idx = map.idx(pos.x, pos.y);
+                        map.revealed_tiles[idx] = true;
                     }
                 }
             }
         }
-
-        commands.remove(*entity);
     }
+
+    // Blocked moves are used up too, so they can't pile up and get saved with the run.
+    commands.remove(*entity);
 }
 
 #[cfg(test)]
@@ -52,10 +70,35 @@ mod test {
     fn test_blocked() {
         let destination = Point::new(0, 1);
         let mut state = MovementSystemTest::new().setup();
-        state.resources.get_mut::<Map>().unwrap().tiles[map_idx(0, 1)] = TileType::Wall;
+        let mut map = state.resources.get_mut::<Map>().unwrap();
+        let idx = map.idx(0, 1);
+        map.tiles[idx] = TileType::Wall;
+        drop(map);
         state.step(state.player, destination);
 
         assert_eq!(state.player_pos(), Point::zero());
+        assert_eq!(<&WantsToMove>::query().iter(&state.world).count(), 0);
+    }
+
+    #[test]
+    fn test_walking_into_door_opens_it() {
+        let destination = Point::new(0, 1);
+        let mut state = MovementSystemTest::new().setup();
+        let mut map = state.resources.get_mut::<Map>().unwrap();
+        let idx = map.idx(0, 1);
+        map.tiles[idx] = TileType::DoorClosed;
+        drop(map);
+
+        state.step(state.player, destination);
+        assert_eq!(state.player_pos(), Point::zero());
+        assert!(state.player_fov().is_dirty);
+        assert_eq!(
+            state.resources.get::<Map>().unwrap().tiles[idx],
+            TileType::DoorOpen
+        );
+
+        state.step(state.player, destination);
+        assert_eq!(state.player_pos(), destination);
     }
 
     #[test]
@@ -143,6 +186,35 @@ mod test {
         assert_eq!(num_tiles(&state), 1);
     }
 
+    #[test]
+    fn test_monsters_do_not_stack() {
+        let mut state = MovementSystemTest::new().setup();
+        let destination = Point::new(3, 3);
+        let first = state.world.push((Enemy, Health::new(1), Point::new(2, 3)));
+        let second = state.world.push((Enemy, Health::new(1), Point::new(4, 3)));
+        state.world.push((
+            (),
+            WantsToMove {
+                entity: first,
+                destination,
+            },
+        ));
+
+        state.step(second, destination);
+
+        let pos = |state: &MovementSystemTest, enemy| {
+            *state
+                .world
+                .entry_ref(enemy)
+                .unwrap()
+                .get_component::<Point>()
+                .unwrap()
+        };
+        assert_eq!(pos(&state, first), destination);
+        assert_eq!(pos(&state, second), Point::new(4, 3));
+        assert_eq!(<&WantsToMove>::query().iter(&state.world).count(), 0);
+    }
+
     struct MovementSystemTest {
         world: World,
         resources: Resources,
@@ -153,12 +225,14 @@ mod test {
         fn new() -> Self {
             let mut world = World::default();
             let mut resources = Resources::default();
-            let map_builder = EmptyArchitect {}.build(&mut RandomNumberGenerator::new());
+            let map_builder =
+                EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut RandomNumberGenerator::new());
             let camera = Camera::new(Point::zero());
             let player = spawn_player(&mut world, Point::zero());
 
             resources.insert(map_builder.map);
             resources.insert(camera);
+            resources.insert(SpatialIndex::default());
 
             Self {
                 world,
@@ -181,6 +255,7 @@ mod test {
             };
 
             self.world.push(((), wants_to_move_component));
+            self.index();
 
             let mut system = movement_system();
             system.run(&mut self.world, &mut self.resources);
@@ -191,6 +266,12 @@ mod test {
                 .flush(&mut self.world, &mut self.resources);
         }
 
+        fn index(&mut self) {
+            let map = self.resources.get::<Map>().unwrap();
+            let mut index = self.resources.get_mut::<SpatialIndex>().unwrap();
+            index.rebuild(&map, &self.world);
+        }
+
         fn player_pos(&mut self) -> Point {
             *self
                 .world
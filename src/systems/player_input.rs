@@ -1,18 +1,34 @@
+use std::collections::HashSet;
+
 use legion::world::SubWorld;
 
 pub use crate::prelude::*;
 
 #[system]
+// Read by `carried_items`, `ranged_weapon` and `Targeting::line_of_fire` rather than a query here.
+#[read_component(Item)]
+#[read_component(Carried)]
+#[read_component(Equipped)]
+#[read_component(Ranged)]
+#[read_component(Damage)]
+#[read_component(Ammo)]
 #[allow(clippy::too_many_arguments)] // Allowing for queries, this function will only be called by automation.
 pub fn player_input(
     ecs: &mut SubWorld,
     players: &mut Query<(Entity, &Point, &Player)>,
-    player_items: &mut Query<(Entity, &Item, &Carried)>,
     items_on_ground: &mut Query<(Entity, &Item, &Point)>,
-    weapons: &mut Query<(Entity, &Carried, &Weapon)>,
-    enemies: &mut Query<(Entity, &Point, &Enemy)>,
+    equippable: &mut Query<&Equippable>,
+    equipped: &mut Query<(&Carried, &Equipped)>,
+    enemies: &mut Query<&Enemy>,
+    names: &mut Query<&Name>,
+    fovs: &mut Query<&FieldOfView>,
+    #[resource] map: &Map,
     #[resource] key: &Option<VirtualKeyCode>,
     #[resource] turn_state: &mut TurnState,
+    #[resource] log: &mut MessageLog,
+    #[resource] inventory: &mut InventoryScreen,
+    #[resource] targeting: &mut Targeting,
+    #[resource] index: &SpatialIndex,
     commands: &mut CommandBuffer,
 ) {
     let (player_entity, player_pos) = players
@@ -21,47 +37,177 @@ pub fn player_input(
         .next()
         .unwrap();
 
-    let mut player_items = player_items
-        .iter(ecs)
-        .filter(|(_entity, _item, carried)| carried.0 == player_entity)
-        .map(|(entity, item, _carried)| (entity, item));
+    let items = carried_items(ecs, player_entity);
+    let fov = fovs.get(ecs, player_entity).unwrap();
+
+    if inventory.is_open() {
+        if let Some(key) = key {
+            if inventory_input(
+                *key,
+                inventory,
+                &items,
+                |item, log, commands| {
+                    activate_item(
+                        ecs,
+                        fov,
+                        targeting,
+                        item,
+                        player_entity,
+                        player_pos,
+                        log,
+                        commands,
+                    )
+                },
+                player_pos,
+                log,
+                commands,
+            ) {
+                *turn_state = TurnState::PlayerTurn;
+            }
+        }
+        return;
+    }
+
+    if targeting.is_active() {
+        let targets = visible_targets(ecs, player_pos, fov);
+        match key {
+            Some(VirtualKeyCode::Escape) => targeting.close(),
+            Some(VirtualKeyCode::Tab) => targeting.cycle(&targets),
+            Some(VirtualKeyCode::Return | VirtualKeyCode::F)
+                if fire(
+                    ecs,
+                    map,
+                    index,
+                    targeting,
+                    player_entity,
+                    player_pos,
+                    log,
+                    commands,
+                ) =>
+            {
+                *turn_state = TurnState::PlayerTurn;
+            }
+            _ => {}
+        }
+        return;
+    }
+
+    // Scrolling the message log is free, so it's handled before anything that takes a turn. The
+    // inventory screen uses the same keys to turn its pages.
+    match key {
+        Some(VirtualKeyCode::PageUp) => return log.scroll(1),
+        Some(VirtualKeyCode::PageDown) => return log.scroll(-1),
+        _ => {}
+    }
 
     if let Some(key) = key {
         let delta = match key {
+            VirtualKeyCode::I => return inventory.open(InventoryMode::Use),
+            VirtualKeyCode::D => return inventory.open(InventoryMode::Drop),
+            VirtualKeyCode::X => return inventory.open(InventoryMode::Examine),
+            VirtualKeyCode::F => {
+                let weapon = ranged_weapon(ecs, player_entity);
+                let out_of_ammo = weapon
+                    .and_then(|weapon| ecs.entry_ref(weapon).ok())
+                    .is_some_and(|weapon| matches!(weapon.get_component::<Ammo>(), Ok(Ammo(0))));
+                match (weapon, visible_targets(ecs, player_pos, fov).first()) {
+                    (None, _) => {
+                        log.add(MessageCategory::Info, "You have nothing to fire or throw.")
+                    }
+                    (Some(_), _) if out_of_ammo => {
+                        log.add(MessageCategory::Info, "You're out of ammunition.")
+                    }
+                    (Some(_), None) => log.add(
+                        MessageCategory::Info,
+                        "There's nothing in sight to shoot at.",
+                    ),
+                    (Some(weapon), Some((target, _))) => targeting.open(weapon, *target),
+                }
+                return;
+            }
             VirtualKeyCode::Up => Point::new(0, -1),
             VirtualKeyCode::Down => Point::new(0, 1),
             VirtualKeyCode::Left => Point::new(-1, 0),
             VirtualKeyCode::Right => Point::new(1, 0),
-            VirtualKeyCode::Key1 => use_item(0, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key2 => use_item(1, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key3 => use_item(2, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key4 => use_item(3, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key5 => use_item(4, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key6 => use_item(5, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key7 => use_item(6, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key8 => use_item(7, &player_entity, &mut player_items, commands),
-            VirtualKeyCode::Key9 => use_item(8, &player_entity, &mut player_items, commands),
+            VirtualKeyCode::Key1
+            | VirtualKeyCode::Key2
+            | VirtualKeyCode::Key3
+            | VirtualKeyCode::Key4
+            | VirtualKeyCode::Key5
+            | VirtualKeyCode::Key6
+            | VirtualKeyCode::Key7
+            | VirtualKeyCode::Key8
+            | VirtualKeyCode::Key9 => {
+                let n = *key as usize - VirtualKeyCode::Key1 as usize;
+                match items.get(n) {
+                    Some((item, _)) => {
+                        let used = activate_item(
+                            ecs,
+                            fov,
+                            targeting,
+                            *item,
+                            player_entity,
+                            player_pos,
+                            log,
+                            commands,
+                        );
+                        if !used {
+                            return;
+                        }
+                    }
+                    None => log.add(
+                        MessageCategory::Info,
+                        format!("You have nothing in slot {}.", n + 1),
+                    ),
+                }
+
+                Point::zero()
+            }
             VirtualKeyCode::G => {
+                // The `Equipped` added for earlier items is still in the command buffer, so the
+                // slots they took have to be remembered here.
+                let mut slots_filled = HashSet::new();
                 for (&entity, &_item, &_pos) in items_on_ground
                     .iter(ecs)
                     .filter(|(_entity, _item, &item_pos)| item_pos == player_pos)
                 {
                     commands.remove_component::<Point>(entity);
                     commands.add_component(entity, Carried(player_entity));
+                    let name = names
+                        .get(ecs, entity)
+                        .map(|name| name.0.clone())
+                        .unwrap_or_default();
+                    log.add(MessageCategory::Item, format!("You pick up the {}.", name));
 
-                    if let Ok(e) = ecs.entry_ref(entity) {
-                        if e.get_component::<Weapon>().is_ok() {
-                            for (entity, _carried, _weapon) in
-                                weapons.iter(ecs).filter(|(_, c, _)| c.0 == player_entity)
-                            {
-                                commands.remove(*entity);
-                            }
+                    // Gear goes straight on if there's nothing in its slot yet.
+                    if let Ok(&Equippable(slot)) = equippable.get(ecs, entity) {
+                        let slot_is_free = !slots_filled.contains(&slot)
+                            && !equipped.iter(ecs).any(|(carried, equipped)| {
+                                carried.0 == player_entity && equipped.0 == slot
+                            });
+                        if slot_is_free {
+                            slots_filled.insert(slot);
+                            commands.add_component(entity, Equipped(slot));
+                            log.add(MessageCategory::Item, format!("You equip the {}.", name));
                         }
                     }
                 }
 
                 Point::zero()
             }
+            VirtualKeyCode::C => {
+                for direction in Map::directions() {
+                    commands.push((
+                        (),
+                        WantsToCloseDoor {
+                            entity: player_entity,
+                            position: player_pos + direction,
+                        },
+                    ));
+                }
+
+                Point::zero()
+            }
             _ => Point::new(0, 0),
         };
 
@@ -72,63 +218,228 @@ pub fn player_input(
             .unwrap();
 
         if delta != Point::zero() {
-            let mut hit_something = false;
-            enemies
-                .iter(ecs)
-                .filter(|(_, pos, _)| **pos == destination)
-                .for_each(|(entity, _, _)| {
-                    hit_something = true;
+            let victim = index
+                .creature_at(destination)
+                .filter(|creature| enemies.get(ecs, *creature).is_ok());
+            match victim {
+                Some(victim) => {
                     commands.push((
                         (),
                         WantsToAttack {
                             attacker: player_entity,
-                            victim: *entity,
+                            victim,
+                        },
+                    ));
+                }
+                None => {
+                    commands.push((
+                        (),
+                        WantsToMove {
+                            entity: player_entity,
+                            destination,
                         },
                     ));
-                });
-
-            if !hit_something {
-                commands.push((
-                    (),
-                    WantsToMove {
-                        entity: player_entity,
-                        destination,
-                    },
-                ));
+                }
             }
         }
 
         *turn_state = TurnState::PlayerTurn;
     }
+}
 
-    fn use_item<'a, I>(
-        n: usize,
-        player_entity: &Entity,
-        player_items: &mut I,
-        commands: &mut CommandBuffer,
-    ) -> Point
-    where
-        I: Iterator<Item = (&'a Entity, &'a Item)>,
-    {
-        println!("Using Item {}", n);
+/// Uses `item`, or starts aiming it if it has to be pointed at something. Returns whether it
+/// used up the turn.
+#[allow(clippy::too_many_arguments)]
+fn activate_item(
+    ecs: &SubWorld,
+    fov: &FieldOfView,
+    targeting: &mut Targeting,
+    item: Entity,
+    player_entity: Entity,
+    player_pos: Point,
+    log: &mut MessageLog,
+    commands: &mut CommandBuffer,
+) -> bool {
+    let aimed = ecs.entry_ref(item).is_ok_and(|item| {
+        item.get_component::<Ranged>().is_ok() && item.get_component::<Damage>().is_err()
+    });
+    if !aimed {
+        commands.push((
+            (),
+            WantsToActivateItem {
+                used_by: player_entity,
+                item,
+            },
+        ));
+        return true;
+    }
 
-        let item_entity = player_items
-            .enumerate()
-            .filter(|(item_count, _)| *item_count == n) // This seems super fragile, but it's consistent with the display I guess
-            .map(|(_, (entity, _))| entity)
-            .next();
+    match visible_targets(ecs, player_pos, fov).first() {
+        Some((target, _)) => targeting.open(item, *target),
+        None => log.add(MessageCategory::Info, "There's nothing in sight to target."),
+    }
+    false
+}
+
+/// Shoots at the current target if nothing's in the way, returning whether it used up the turn.
+#[allow(clippy::too_many_arguments)]
+fn fire(
+    ecs: &SubWorld,
+    map: &Map,
+    index: &SpatialIndex,
+    targeting: &mut Targeting,
+    player_entity: Entity,
+    player_pos: Point,
+    log: &mut MessageLog,
+    commands: &mut CommandBuffer,
+) -> bool {
+    let (weapon, victim) = match (targeting.weapon, targeting.target) {
+        (Some(weapon), Some(target)) => (weapon, target),
+        _ => return false,
+    };
+    if !targeting
+        .line_of_fire(ecs, map, index, player_pos)
+        .is_some_and(|line| line.is_clear())
+    {
+        log.add(MessageCategory::Info, "You don't have a clear shot.");
+        return false;
+    }
 
-        if let Some(item_entity) = item_entity {
-            println!("Pushing Command");
+    // Scrolls and the like are activated at the target's feet instead of hitting it.
+    let is_weapon = ecs
+        .entry_ref(weapon)
+        .is_ok_and(|weapon| weapon.get_component::<Damage>().is_ok());
+    let target_pos = ecs
+        .entry_ref(victim)
+        .ok()
+        .and_then(|victim| victim.get_component::<Point>().ok().copied());
+    match target_pos {
+        Some(_) if is_weapon => {
+            commands.push((
+                (),
+                WantsToAttack {
+                    attacker: player_entity,
+                    victim,
+                },
+                RangedAttack { weapon },
+            ));
+        }
+        Some(target_pos) => {
             commands.push((
                 (),
                 WantsToActivateItem {
-                    used_by: *player_entity,
-                    item: *item_entity,
+                    used_by: player_entity,
+                    item: weapon,
                 },
+                TargetedAt(target_pos),
             ));
         }
+        None => return false,
+    }
+
+    targeting.close();
+    true
+}
+
+/// Handles a key while the inventory screen is open, returning whether it used up the turn.
+/// `activate` uses an item and says whether that took the turn.
+fn inventory_input(
+    key: VirtualKeyCode,
+    inventory: &mut InventoryScreen,
+    items: &[(Entity, String)],
+    activate: impl FnOnce(Entity, &mut MessageLog, &mut CommandBuffer) -> bool,
+    player_pos: Point,
+    log: &mut MessageLog,
+    commands: &mut CommandBuffer,
+) -> bool {
+    match key {
+        VirtualKeyCode::Escape if inventory.examining.is_some() => inventory.examining = None,
+        VirtualKeyCode::Escape => inventory.close(),
+        VirtualKeyCode::Tab => {
+            let mode = inventory.mode.unwrap_or(InventoryMode::Use).next();
+            inventory.mode = Some(mode);
+            inventory.examining = None;
+        }
+        VirtualKeyCode::PageUp => inventory.turn_page(-1, items.len()),
+        VirtualKeyCode::PageDown => inventory.turn_page(1, items.len()),
+        _ if inventory.examining.is_some() => {}
+        _ => {
+            let item = match inventory.item_for_key(items, key) {
+                Some(item) => item,
+                None => return false,
+            };
+            let name = items
+                .iter()
+                .find(|(entity, _)| *entity == item)
+                .map(|(_, name)| name.as_str())
+                .unwrap_or_default();
+
+            match inventory.mode {
+                Some(InventoryMode::Use) => {
+                    inventory.close();
+                    return activate(item, log, commands);
+                }
+                Some(InventoryMode::Drop) => {
+                    commands.remove_component::<Carried>(item);
+                    commands.remove_component::<Equipped>(item);
+                    commands.add_component(item, player_pos);
+                    log.add(MessageCategory::Item, format!("You drop the {}.", name));
+                }
+                Some(InventoryMode::Examine) => {
+                    inventory.examining = Some(item);
+                    return false;
+                }
+                None => return false,
+            }
+
+            inventory.close();
+            return true;
+        }
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_picking_up_two_items_for_one_slot_only_equips_one() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let map = Map::new(MAP_WIDTH, MAP_HEIGHT);
+        let player = spawn_player(&mut world, Point::zero());
+        let sword = |name: &str| {
+            (
+                Item,
+                Name(name.to_string()),
+                Equippable(EquipmentSlot::MainHand),
+                Point::zero(),
+            )
+        };
+        world.push(sword("Short Sword"));
+        world.push(sword("Long Sword"));
+        let mut index = SpatialIndex::default();
+        index.rebuild(&map, &world);
+        resources.insert(map);
+        resources.insert(index);
+        resources.insert(Some(VirtualKeyCode::G));
+        resources.insert(TurnState::AwaitingInput);
+        resources.insert(MessageLog::default());
+        resources.insert(InventoryScreen::default());
+        resources.insert(Targeting::default());
+
+        let mut schedule = Schedule::builder()
+            .add_system(player_input_system())
+            .build();
+        schedule.execute(&mut world, &mut resources);
 
-        Point::zero()
+        let carried = <&Carried>::query()
+            .iter(&world)
+            .filter(|carried| carried.0 == player)
+            .count();
+        assert_eq!(carried, 2);
+        assert_eq!(<&Equipped>::query().iter(&world).count(), 1);
     }
 }
@@ -1,3 +1,4 @@
+use super::fleeing::is_fleeing;
 use crate::prelude::*;
 
 #[system]
@@ -5,47 +6,45 @@ use crate::prelude::*;
 #[read_component(MovingRandomly)]
 #[read_component(Health)]
 #[read_component(Player)]
-pub fn random_move(ecs: &mut SubWorld, commands: &mut CommandBuffer) {
+#[read_component(FleesBelow)]
+pub fn random_move(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] index: &SpatialIndex,
+) {
     let mut movers = <(Entity, &Point, &MovingRandomly)>::query();
-    let mut positions = <(Entity, &Point, &Health)>::query();
 
-    movers.iter(ecs).for_each(|(entity, pos, _)| {
-        let mut rng = RandomNumberGenerator::new();
-        let destination = match rng.range(0, 4) {
-            0 => Point::new(-1, 0),
-            1 => Point::new(1, 0),
-            2 => Point::new(0, -1),
-            _ => Point::new(0, 1),
-        } + *pos;
+    movers
+        .iter(ecs)
+        .filter(|(entity, _, _)| !is_fleeing(ecs, **entity))
+        .for_each(|(entity, pos, _)| {
+            let destination = match rng.range(0, 4) {
+                0 => Point::new(-1, 0),
+                1 => Point::new(1, 0),
+                2 => Point::new(0, -1),
+                _ => Point::new(0, 1),
+            } + *pos;
 
-        let mut attacked = false;
+            let victim = index.creature_at(destination).filter(|victim| {
+                ecs.entry_ref(*victim)
+                    .is_ok_and(|entry| entry.get_component::<Player>().is_ok())
+            });
 
-        positions
-            .iter(ecs)
-            .filter(|(_, target_pos, _)| **target_pos == destination)
-            .for_each(|(victim, _, _)| {
-                if ecs
-                    .entry_ref(*victim)
-                    .unwrap()
-                    .get_component::<Player>()
-                    .is_ok()
-                {
-                    println!("Attacking player?");
-                    let victim = *victim;
+            match victim {
+                Some(victim) => {
                     let attacker = *entity;
                     commands.push(((), WantsToAttack { attacker, victim }));
-                    attacked = true;
                 }
-            });
-
-        if !attacked {
-            commands.push((
-                (),
-                WantsToMove {
-                    entity: *entity,
-                    destination,
-                },
-            ));
-        }
-    });
+                None => {
+                    commands.push((
+                        (),
+                        WantsToMove {
+                            entity: *entity,
+                            destination,
+                        },
+                    ));
+                }
+            }
+        });
 }
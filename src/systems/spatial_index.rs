@@ -0,0 +1,9 @@
+use crate::prelude::*;
+
+/// Indexes where everything is before anything this turn goes looking for it.
+#[system]
+#[read_component(Point)]
+#[read_component(Health)]
+pub fn spatial_index(ecs: &SubWorld, #[resource] map: &Map, #[resource] index: &mut SpatialIndex) {
+    index.rebuild(map, ecs);
+}
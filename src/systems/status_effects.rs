@@ -0,0 +1,296 @@
+use crate::prelude::*;
+
+/// Ticks the status effects of whoever's turn it is: the player during `PlayerTurn`, and the
+/// monsters during `MonsterTurn`. Runs after intents have been queued, so slow and confusion can
+/// cancel or redirect them before combat and movement see them.
+#[system]
+#[read_component(Player)]
+#[read_component(Name)]
+#[read_component(Point)]
+#[read_component(StatusEffects)]
+#[read_component(WantsToMove)]
+#[read_component(WantsToAttack)]
+#[write_component(Health)]
+pub fn status_effects(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] turn_state: &TurnState,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] log: &mut MessageLog,
+) {
+    let players_turn = *turn_state == TurnState::PlayerTurn;
+    // Monsters parked on another level have no `Point`, and their effects wait until it's back.
+    let affected: Vec<(Entity, StatusEffects, bool)> =
+        <(Entity, &StatusEffects, Option<&Player>)>::query()
+            .filter(component::<Point>())
+            .iter(ecs)
+            .filter(|(_, _, player)| player.is_some() == players_turn)
+            .map(|(entity, effects, player)| (*entity, effects.clone(), player.is_some()))
+            .collect();
+
+    for (entity, mut effects, is_player) in affected {
+        let name = display_name(ecs, entity);
+        let category = if is_player {
+            MessageCategory::Danger
+        } else {
+            MessageCategory::Combat
+        };
+
+        let health_change: i32 = effects
+            .0
+            .iter()
+            .map(|effect| match effect.kind {
+                StatusKind::Poison => -1,
+                StatusKind::Regeneration => 1,
+                _ => 0,
+            })
+            .sum();
+        if let Ok(health) = ecs.entry_mut(entity).unwrap().get_component_mut::<Health>() {
+            health.current = i32::min(health.max, health.current + health_change);
+            if effects.has(StatusKind::Poison) && is_player {
+                log.add(category, "The poison burns in your veins.");
+            }
+            if health.current < 1 && !is_player {
+                commands.remove(entity);
+                log.add(category, format!("{} dies.", capitalize(&name)));
+                continue;
+            }
+        }
+
+        let slow = effects
+            .0
+            .iter()
+            .any(|effect| effect.kind == StatusKind::Slow && effect.turns % 2 == 0);
+        if slow {
+            cancel_intents(ecs, commands, entity);
+            if is_player {
+                log.add(MessageCategory::Info, "You are too slow to act.");
+            }
+        } else if effects.has(StatusKind::Confusion) {
+            let pos = match ecs
+                .entry_ref(entity)
+                .ok()
+                .and_then(|entry| entry.get_component::<Point>().ok().copied())
+            {
+                Some(pos) => pos,
+                None => continue,
+            };
+            for (message, _) in <(Entity, &WantsToMove)>::query()
+                .iter(ecs)
+                .filter(|(_, want_move)| want_move.entity == entity)
+            {
+                let destination = pos + Map::directions()[rng.range(0, 4)];
+                commands.add_component(
+                    *message,
+                    WantsToMove {
+                        entity,
+                        destination,
+                    },
+                );
+            }
+        }
+
+        for effect in effects.0.iter_mut() {
+            effect.turns -= 1;
+            if effect.turns < 1 && is_player {
+                log.add(
+                    MessageCategory::Info,
+                    format!("You are no longer {}.", effect.kind.name()),
+                );
+            }
+        }
+        effects.0.retain(|effect| effect.turns > 0);
+        if effects.0.is_empty() {
+            commands.remove_component::<StatusEffects>(entity);
+        } else {
+            commands.add_component(entity, effects);
+        }
+    }
+}
+
+fn cancel_intents(ecs: &SubWorld, commands: &mut CommandBuffer, entity: Entity) {
+    for (message, _) in <(Entity, &WantsToMove)>::query()
+        .iter(ecs)
+        .filter(|(_, want_move)| want_move.entity == entity)
+    {
+        commands.remove(*message);
+    }
+    for (message, _) in <(Entity, &WantsToAttack)>::query()
+        .iter(ecs)
+        .filter(|(_, attack)| attack.attacker == entity)
+    {
+        commands.remove(*message);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    struct StatusTest {
+        world: World,
+        resources: Resources,
+        player: Entity,
+    }
+
+    impl StatusTest {
+        fn new(effects: &[StatusEffect]) -> Self {
+            let mut world = World::default();
+            let mut resources = Resources::default();
+            resources.insert(TurnState::PlayerTurn);
+            resources.insert(RandomNumberGenerator::seeded(1));
+            resources.insert(MessageLog::default());
+            let player = spawn_player(&mut world, Point::new(5, 5));
+            world
+                .entry(player)
+                .unwrap()
+                .add_component(StatusEffects(effects.to_vec()));
+
+            Self {
+                world,
+                resources,
+                player,
+            }
+        }
+
+        fn step(&mut self) {
+            let mut schedule = Schedule::builder()
+                .add_system(status_effects_system())
+                .build();
+            schedule.execute(&mut self.world, &mut self.resources);
+        }
+
+        fn health(&self) -> i32 {
+            self.world
+                .entry_ref(self.player)
+                .unwrap()
+                .get_component::<Health>()
+                .unwrap()
+                .current
+        }
+
+        fn effects(&self) -> Option<StatusEffects> {
+            self.world
+                .entry_ref(self.player)
+                .unwrap()
+                .get_component::<StatusEffects>()
+                .ok()
+                .cloned()
+        }
+
+        fn queue_move(&mut self, destination: Point) -> Entity {
+            let entity = self.player;
+            self.world.push((
+                (),
+                WantsToMove {
+                    entity,
+                    destination,
+                },
+            ))
+        }
+    }
+
+    #[test]
+    fn test_poison_and_regeneration_tick_then_wear_off() {
+        let mut test = StatusTest::new(&[
+            StatusEffect::new(StatusKind::Poison, 3),
+            StatusEffect::new(StatusKind::Regeneration, 1),
+        ]);
+
+        test.step();
+        assert_eq!(test.health(), 10);
+        assert_eq!(
+            test.effects(),
+            Some(StatusEffects(vec![StatusEffect::new(
+                StatusKind::Poison,
+                2
+            )]))
+        );
+
+        test.step();
+        test.step();
+        assert_eq!(test.health(), 8);
+        assert_eq!(test.effects(), None);
+    }
+
+    #[test]
+    fn test_only_the_side_whose_turn_it_is_ticks() {
+        let mut test = StatusTest::new(&[StatusEffect::new(StatusKind::Poison, 3)]);
+        test.resources.insert(TurnState::MonsterTurn);
+
+        test.step();
+
+        assert_eq!(test.health(), 10);
+    }
+
+    #[test]
+    fn test_confusion_sends_moves_astray() {
+        let mut test = StatusTest::new(&[StatusEffect::new(StatusKind::Confusion, 20)]);
+
+        let destinations: Vec<Point> = (0..10)
+            .map(|_| {
+                let message = test.queue_move(Point::new(5, 4));
+                test.step();
+                let entry = test.world.entry_ref(message).unwrap();
+                entry.get_component::<WantsToMove>().unwrap().destination
+            })
+            .collect();
+
+        assert!(destinations
+            .iter()
+            .all(|pos| DistanceAlg::Manhattan.distance2d(*pos, Point::new(5, 5)) == 1.0));
+        assert!(destinations.iter().any(|pos| *pos != Point::new(5, 4)));
+    }
+
+    #[test]
+    fn test_monsters_parked_on_another_level_are_left_alone() {
+        let mut test = StatusTest::new(&[]);
+        let effects = StatusEffects(vec![
+            StatusEffect::new(StatusKind::Confusion, 4),
+            StatusEffect::new(StatusKind::Poison, 4),
+        ]);
+        let monster = test.world.push((
+            Enemy,
+            Name("Goblin".to_string()),
+            Health::new(1),
+            effects.clone(),
+            Point::new(7, 5),
+        ));
+        park_level(&mut test.world, &mut test.resources, 0);
+        test.world.push((
+            (),
+            WantsToMove {
+                entity: monster,
+                destination: Point::new(8, 5),
+            },
+        ));
+        test.resources.insert(TurnState::MonsterTurn);
+
+        test.step();
+
+        let entry = test.world.entry_ref(monster).unwrap();
+        assert_eq!(entry.get_component::<Health>().unwrap().current, 1);
+        assert_eq!(entry.get_component::<StatusEffects>().unwrap(), &effects);
+        assert!(test
+            .resources
+            .get::<MessageLog>()
+            .unwrap()
+            .visible(10)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_slow_skips_every_other_turn() {
+        let mut test = StatusTest::new(&[StatusEffect::new(StatusKind::Slow, 4)]);
+
+        let moved: Vec<bool> = (0..4)
+            .map(|_| {
+                let message = test.queue_move(Point::new(5, 4));
+                test.step();
+                test.world.entry_ref(message).is_ok()
+            })
+            .collect();
+
+        assert_eq!(moved, vec![false, true, false, true]);
+    }
+}
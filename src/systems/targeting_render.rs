@@ -0,0 +1,74 @@
+use crate::prelude::*;
+
+#[system]
+#[read_component(Player)]
+#[read_component(Point)]
+#[read_component(Ranged)]
+#[read_component(Name)]
+pub fn targeting_render(
+    ecs: &SubWorld,
+    #[resource] targeting: &Targeting,
+    #[resource] map: &Map,
+    #[resource] camera: &Camera,
+    #[resource] index: &SpatialIndex,
+) {
+    if !targeting.is_active() {
+        return;
+    }
+
+    let player_pos = *<&Point>::query()
+        .filter(component::<Player>())
+        .iter(ecs)
+        .next()
+        .unwrap();
+    let line = match targeting.line_of_fire(ecs, map, index, player_pos) {
+        Some(line) => line,
+        None => return,
+    };
+
+    let mut draw_batch = DrawBatch::new();
+    draw_batch.target(LAYER_ENTITIES);
+    let offset = camera.offset();
+
+    // Everything from the first blocked tile on is drawn in red, so it's clear where the shot
+    // would stop.
+    let mut color = GREEN;
+    for pos in line.path.iter() {
+        if Some(*pos) == line.blocked_at {
+            color = RED;
+        }
+        // Leave the target and anything standing in the way visible.
+        if index.creature_at(*pos).is_none() {
+            draw_batch.set(*pos - offset, ColorPair::new(color, BLACK), to_cp437('*'));
+        }
+    }
+    draw_batch.submit(5100).expect("Targeting draw batch error");
+
+    let target_name = targeting
+        .target
+        .and_then(|target| ecs.entry_ref(target).ok())
+        .and_then(|target| {
+            target
+                .get_component::<Name>()
+                .ok()
+                .map(|name| name.0.clone())
+        })
+        .unwrap_or_default();
+    let (status, color) = if line.is_clear() {
+        ("", YELLOW)
+    } else {
+        (" (no clear shot)", RED)
+    };
+
+    let mut hud_batch = DrawBatch::new();
+    hud_batch.target(LAYER_HUD);
+    hud_batch.print_color_centered(
+        3,
+        format!(
+            "Targeting the {}{} - Tab: next target, Enter/F: fire, Escape: cancel",
+            target_name, status
+        ),
+        ColorPair::new(color, BLACK),
+    );
+    hud_batch.submit(10150).expect("Targeting draw batch error");
+}
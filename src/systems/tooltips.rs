@@ -6,8 +6,15 @@ use crate::prelude::*;
 #[read_component(Health)]
 #[read_component(FieldOfView)]
 #[read_component(Player)]
-pub fn tooltips(ecs: &SubWorld, #[resource] mouse_pos: &Point, #[resource] camera: &Camera) {
-    let mut positions = <(Entity, &Point, &Name)>::query();
+#[read_component(Armor)]
+#[read_component(Carried)]
+#[read_component(Equipped)]
+pub fn tooltips(
+    ecs: &SubWorld,
+    #[resource] mouse_pos: &Point,
+    #[resource] camera: &Camera,
+    #[resource] index: &SpatialIndex,
+) {
     let mut fov = <&FieldOfView>::query().filter(component::<Player>());
     let player_fov = fov.iter(ecs).next().unwrap();
 
@@ -17,18 +24,34 @@ pub fn tooltips(ecs: &SubWorld, #[resource] mouse_pos: &Point, #[resource] camer
     let mut draw_batch = DrawBatch::new();
     draw_batch.target(LAYER_HUD);
 
-    positions
-        .iter(ecs)
-        .filter(|(_, pos, _)| **pos == map_pos && player_fov.visible_tiles.contains(*pos))
-        .for_each(|(entity, _, name)| {
-            let screen_pos = *mouse_pos * 4;
-            let display =
-                if let Ok(health) = ecs.entry_ref(*entity).unwrap().get_component::<Health>() {
-                    format!("{} : {} hp", &name.0, health.current)
-                } else {
-                    name.0.clone()
-                };
-            draw_batch.print(screen_pos, &display);
-        });
+    let hovered = if player_fov.visible_tiles.contains(&map_pos) {
+        index.entities_at(map_pos)
+    } else {
+        &[]
+    };
+
+    for entity in hovered {
+        let screen_pos = *mouse_pos * 4;
+        // Anything killed since the index was last rebuilt won't have an entry any more.
+        let entry = match ecs.entry_ref(*entity) {
+            Ok(entry) => entry,
+            Err(_) => continue,
+        };
+        let name = match entry.get_component::<Name>() {
+            Ok(name) => name,
+            Err(_) => continue,
+        };
+        let display = if let Ok(health) = entry.get_component::<Health>() {
+            match defence(ecs, *entity) {
+                0 => format!("{} : {} hp", &name.0, health.current),
+                defence => format!("{} : {} hp, {} defence", &name.0, health.current, defence),
+            }
+        } else if let Ok(armor) = entry.get_component::<Armor>() {
+            format!("{} : {} defence", &name.0, armor.0)
+        } else {
+            name.0.clone()
+        };
+        draw_batch.print(screen_pos, &display);
+    }
     draw_batch.submit(10100).expect("Tooltip Batch Draw Error");
 }
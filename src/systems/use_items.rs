@@ -2,35 +2,319 @@ use crate::prelude::*;
 
 #[system]
 #[read_component(WantsToActivateItem)]
-#[read_component(ProvidesHealing)]
-#[read_component(ProvidesDungeonMap)]
+#[read_component(TargetedAt)]
+#[read_component(Provides)]
+#[read_component(AreaOfEffect)]
+#[read_component(Ranged)]
+#[read_component(StatusEffects)]
+#[read_component(Name)]
+#[read_component(Player)]
+#[read_component(Enemy)]
+#[read_component(Point)]
+#[read_component(FieldOfView)]
+#[read_component(Carried)]
+#[read_component(Equippable)]
+#[read_component(Equipped)]
 #[write_component(Health)] // !
-pub fn use_items(ecs: &mut SubWorld, commands: &mut CommandBuffer, #[resource] map: &mut Map) {
-    let mut healing_to_apply = Vec::<(Entity, i32)>::new();
+pub fn use_items(
+    ecs: &mut SubWorld,
+    commands: &mut CommandBuffer,
+    #[resource] map: &mut Map,
+    #[resource] index: &SpatialIndex,
+    #[resource] rng: &mut RandomNumberGenerator,
+    #[resource] log: &mut MessageLog,
+) {
+    let activations: Vec<(Entity, WantsToActivateItem, Option<TargetedAt>)> =
+        <(Entity, &WantsToActivateItem, Option<&TargetedAt>)>::query()
+            .iter(ecs)
+            .map(|(entity, activate, targeted_at)| (*entity, *activate, targeted_at.copied()))
+            .collect();
 
-    for (entity, activate) in <(Entity, &WantsToActivateItem)>::query().iter(ecs) {
-        let item = ecs.entry_ref(activate.item).unwrap();
-
-        if let Ok(healing) = item.get_component::<ProvidesHealing>() {
-            healing_to_apply.push((activate.used_by, healing.amount));
+    for (entity, activate, targeted_at) in activations {
+        let provides = ecs
+            .entry_ref(activate.item)
+            .ok()
+            .and_then(|item| item.get_component::<Provides>().ok().cloned());
+        let mut ctx = EffectContext {
+            ecs,
+            commands,
+            map,
+            index,
+            rng,
+            log,
+            user: activate.used_by,
+            item: activate.item,
+            target: targeted_at.map(|TargetedAt(pos)| pos),
+        };
+        for provided in provides.iter().flat_map(|provides| provides.0.iter()) {
+            // Templates are checked when they're loaded, so this only skips effects that have
+            // been removed since a save was made.
+            if let Some(effect) = find_effect(&provided.effect) {
+                (effect.apply)(&mut ctx, provided.amount);
+            }
         }
 
-        if let Ok(_mapper) = item.get_component::<ProvidesDungeonMap>() {
-            for tile in map.revealed_tiles.iter_mut() {
-                *tile = true;
-            }
+        // Gear is put on or taken off instead of being used up.
+        let slot = ecs
+            .entry_ref(activate.item)
+            .ok()
+            .and_then(|item| item.get_component::<Equippable>().ok().copied());
+        if let Some(Equippable(slot)) = slot {
+            toggle_equipped(ecs, commands, log, activate.used_by, activate.item, slot);
+        } else {
+            commands.remove(activate.item);
         }
+        commands.remove(entity);
+    }
+}
+
+/// Takes `item` off if it's equipped. Otherwise puts it on, returning whatever was already in its
+/// slot to the owner's pack.
+fn toggle_equipped(
+    ecs: &SubWorld,
+    commands: &mut CommandBuffer,
+    log: &mut MessageLog,
+    owner: Entity,
+    item: Entity,
+    slot: EquipmentSlot,
+) {
+    let owner_name = display_name(ecs, owner);
+    let item_name = display_name(ecs, item);
+    let is_equipped = ecs
+        .entry_ref(item)
+        .is_ok_and(|item| item.get_component::<Equipped>().is_ok());
 
-        commands.remove(activate.item);
-        commands.remove(*entity);
+    if is_equipped {
+        commands.remove_component::<Equipped>(item);
+        log.add(
+            MessageCategory::Item,
+            format!("{} {}.", subject_verb(&owner_name, "take off"), item_name),
+        );
+        return;
     }
 
-    for heal in healing_to_apply.iter() {
-        if let Ok(mut target) = ecs.entry_mut(heal.0) {
-            if let Ok(health) = target.get_component_mut::<Health>() {
-                println!("Healing");
-                health.current = i32::min(health.max, health.current + heal.1);
-            }
-        }
+    for (old_item, _, _) in <(Entity, &Carried, &Equipped)>::query()
+        .iter(ecs)
+        .filter(|(_, carried, equipped)| carried.0 == owner && equipped.0 == slot)
+    {
+        commands.remove_component::<Equipped>(*old_item);
+        log.add(
+            MessageCategory::Item,
+            format!(
+                "{} {} away.",
+                subject_verb(&owner_name, "put"),
+                display_name(ecs, *old_item)
+            ),
+        );
+    }
+    commands.add_component(item, Equipped(slot));
+    log.add(
+        MessageCategory::Item,
+        format!("{} {}.", subject_verb(&owner_name, "equip"), item_name),
+    );
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::systems::{movement_system, spatial_index_system};
+    use empty::EmptyArchitect;
+
+    fn activate(world: &mut World, resources: &mut Resources, used_by: Entity, item: Entity) {
+        world.push(((), WantsToActivateItem { used_by, item }));
+        let mut schedule = Schedule::builder()
+            .add_system(spatial_index_system())
+            .add_system(use_items_system())
+            .build();
+        schedule.execute(world, resources);
+    }
+
+    fn aim(world: &mut World, resources: &mut Resources, used_by: Entity, item: Entity, at: Point) {
+        world.push(((), WantsToActivateItem { used_by, item }, TargetedAt(at)));
+        let mut schedule = Schedule::builder()
+            .add_system(spatial_index_system())
+            .add_system(use_items_system())
+            .flush()
+            .add_system(movement_system())
+            .build();
+        schedule.execute(world, resources);
+    }
+
+    /// An open, all-floor level with the player in it.
+    fn spell_test() -> (World, Resources, Entity) {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        let mut rng = RandomNumberGenerator::seeded(1);
+        resources.insert(EmptyArchitect {}.build(MAP_WIDTH, MAP_HEIGHT, &mut rng).map);
+        resources.insert(rng);
+        resources.insert(Camera::new(Point::zero()));
+        resources.insert(MessageLog::default());
+        resources.insert(SpatialIndex::default());
+        let player = spawn_player(&mut world, Point::new(5, 5));
+
+        (world, resources, player)
+    }
+
+    fn monster(world: &mut World, pos: Point, hp: i32) -> Entity {
+        world.push((Enemy, Name("Orc".to_string()), Health::new(hp), pos))
+    }
+
+    fn health(world: &World, entity: Entity) -> Option<i32> {
+        let entry = world.entry_ref(entity).ok()?;
+        entry
+            .get_component::<Health>()
+            .ok()
+            .map(|health| health.current)
+    }
+
+    fn equipped_items(world: &World) -> Vec<Entity> {
+        <Entity>::query()
+            .filter(component::<Equipped>())
+            .iter(world)
+            .copied()
+            .collect()
+    }
+
+    #[test]
+    fn test_equipping_swaps_with_the_item_in_the_same_slot() {
+        let mut world = World::default();
+        let mut resources = Resources::default();
+        resources.insert(Map::new(MAP_WIDTH, MAP_HEIGHT));
+        resources.insert(RandomNumberGenerator::seeded(1));
+        resources.insert(MessageLog::default());
+        resources.insert(SpatialIndex::default());
+        let player = spawn_player(&mut world, Point::zero());
+        let gear = |slot| {
+            (
+                Item,
+                Carried(player),
+                Damage(Dice::flat(1)),
+                Equippable(slot),
+            )
+        };
+        let rusty = world.push(gear(EquipmentSlot::MainHand));
+        let shiny = world.push(gear(EquipmentSlot::MainHand));
+        let ring = world.push(gear(EquipmentSlot::Ring));
+
+        activate(&mut world, &mut resources, player, rusty);
+        activate(&mut world, &mut resources, player, ring);
+        assert_eq!(equipped_items(&world).len(), 2);
+
+        activate(&mut world, &mut resources, player, shiny);
+        let equipped = equipped_items(&world);
+        assert!(equipped.contains(&shiny) && equipped.contains(&ring));
+        assert!(!equipped.contains(&rusty));
+        assert_eq!(
+            world
+                .entry(rusty)
+                .unwrap()
+                .get_component::<Carried>()
+                .unwrap(),
+            &Carried(player)
+        );
+
+        activate(&mut world, &mut resources, player, ring);
+        assert_eq!(equipped_items(&world), vec![shiny]);
+    }
+
+    #[test]
+    fn test_fireball_burns_everything_in_its_radius() {
+        let (mut world, mut resources, player) = spell_test();
+        let centre = monster(&mut world, Point::new(10, 10), 10);
+        let corner = monster(&mut world, Point::new(11, 11), 10);
+        let outside = monster(&mut world, Point::new(12, 10), 10);
+        let scroll = world.push((
+            Item,
+            Carried(player),
+            Ranged { range: 6 },
+            Provides(vec![ProvidedEffect::new("Fireball", 3)]),
+            AreaOfEffect(1),
+        ));
+
+        aim(
+            &mut world,
+            &mut resources,
+            player,
+            scroll,
+            Point::new(10, 10),
+        );
+
+        assert_eq!(health(&world, centre), Some(7));
+        assert_eq!(health(&world, corner), Some(7));
+        assert_eq!(health(&world, outside), Some(10));
+        assert_eq!(health(&world, player), Some(10));
+        assert!(world.entry_ref(scroll).is_err());
+    }
+
+    #[test]
+    fn test_lightning_strikes_the_nearest_visible_enemy() {
+        let (mut world, mut resources, player) = spell_test();
+        let hidden = monster(&mut world, Point::new(6, 5), 3);
+        let near = monster(&mut world, Point::new(7, 5), 3);
+        let far = monster(&mut world, Point::new(9, 5), 3);
+        world
+            .entry(player)
+            .unwrap()
+            .get_component_mut::<FieldOfView>()
+            .unwrap()
+            .visible_tiles
+            .extend([Point::new(7, 5), Point::new(9, 5)]);
+        let scroll = world.push((
+            Item,
+            Carried(player),
+            Provides(vec![ProvidedEffect::new("Lightning", 5)]),
+        ));
+
+        activate(&mut world, &mut resources, player, scroll);
+
+        assert!(world.entry_ref(near).is_err());
+        assert_eq!(health(&world, hidden), Some(3));
+        assert_eq!(health(&world, far), Some(3));
+    }
+
+    #[test]
+    fn test_teleport_moves_the_reader_to_a_free_floor_tile() {
+        let (mut world, mut resources, player) = spell_test();
+        let scroll = world.push((
+            Item,
+            Carried(player),
+            Provides(vec![ProvidedEffect::new("Teleport", 1)]),
+        ));
+
+        aim(&mut world, &mut resources, player, scroll, Point::new(5, 5));
+
+        let pos = *world
+            .entry_ref(player)
+            .unwrap()
+            .get_component::<Point>()
+            .unwrap();
+        let map = resources.get::<Map>().unwrap();
+        assert_ne!(pos, Point::new(5, 5));
+        assert_eq!(map.try_tile(pos), Some(TileType::Floor));
+    }
+
+    #[test]
+    fn test_aimed_status_scrolls_affect_the_target() {
+        let (mut world, mut resources, player) = spell_test();
+        let orc = monster(&mut world, Point::new(8, 5), 5);
+        let scroll = world.push((
+            Item,
+            Carried(player),
+            Ranged { range: 6 },
+            Provides(vec![ProvidedEffect::new("Confusion", 4)]),
+        ));
+
+        aim(&mut world, &mut resources, player, scroll, Point::new(8, 5));
+
+        let effects = |entity| {
+            world
+                .entry_ref(entity)
+                .unwrap()
+                .get_component::<StatusEffects>()
+                .ok()
+                .cloned()
+        };
+        assert!(effects(orc).is_some_and(|effects| effects.has(StatusKind::Confusion)));
+        assert_eq!(effects(player), None);
     }
 }
@@ -0,0 +1,242 @@
+use crate::prelude::*;
+
+/// The targeting mode for ranged attacks. While it's active, `player_input` sends every key to it
+/// instead of moving the player.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Targeting {
+    /// What's being fired or thrown.
+    pub weapon: Option<Entity>,
+    pub target: Option<Entity>,
+}
+
+impl Targeting {
+    pub fn open(&mut self, weapon: Entity, target: Entity) {
+        *self = Self {
+            weapon: Some(weapon),
+            target: Some(target),
+        };
+    }
+
+    pub fn close(&mut self) {
+        *self = Self::default();
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.weapon.is_some()
+    }
+
+    /// Moves on to the next of `targets`, wrapping back round to the nearest.
+    pub fn cycle(&mut self, targets: &[(Entity, Point)]) {
+        let current = targets
+            .iter()
+            .position(|(entity, _)| Some(*entity) == self.target);
+        let next = match current {
+            Some(i) => (i + 1) % targets.len(),
+            None => 0,
+        };
+        self.target = targets.get(next).map(|(entity, _)| *entity);
+    }
+
+    /// The shot from `from` at the current target, if there is one.
+    pub fn line_of_fire<S: EntityStore>(
+        &self,
+        ecs: &S,
+        map: &Map,
+        index: &SpatialIndex,
+        from: Point,
+    ) -> Option<LineOfFire> {
+        let target = ecs.entry_ref(self.target?).ok()?;
+        let to = *target.get_component::<Point>().ok()?;
+        let weapon = ecs.entry_ref(self.weapon?).ok()?;
+        let range = weapon.get_component::<Ranged>().ok()?.range;
+
+        Some(LineOfFire::new(map, from, to, range, |pos| {
+            index.creature_at(pos).is_some()
+        }))
+    }
+}
+
+/// Every enemy `fov` can see, nearest to `from` first.
+pub fn visible_targets<S: EntityStore>(
+    ecs: &S,
+    from: Point,
+    fov: &FieldOfView,
+) -> Vec<(Entity, Point)> {
+    let mut targets: Vec<(Entity, Point)> = <(Entity, &Point)>::query()
+        .filter(component::<Enemy>())
+        .iter(ecs)
+        .filter(|(_, pos)| fov.visible_tiles.contains(pos))
+        .map(|(entity, pos)| (*entity, *pos))
+        .collect();
+    targets.sort_by_key(|(_, pos)| {
+        let delta = *pos - from;
+        (delta.x * delta.x + delta.y * delta.y, pos.y, pos.x)
+    });
+    targets
+}
+
+/// What `owner` shoots with: an equipped ranged weapon, or failing that something to throw.
+pub fn ranged_weapon<S: EntityStore>(ecs: &S, owner: Entity) -> Option<Entity> {
+    let mut weapons: Vec<(Entity, bool, bool)> = <(
+        Entity,
+        &Carried,
+        &Ranged,
+        Option<&Equipped>,
+        Option<&Equippable>,
+    )>::query()
+    .filter(component::<Damage>())
+    .iter(ecs)
+    .filter(|(_, carried, _, _, _)| carried.0 == owner)
+    .map(|(entity, _, _, equipped, equippable)| (*entity, equipped.is_some(), equippable.is_none()))
+    .collect();
+    // Equipped weapons first, then throwables. Bows left in the pack can't be fired.
+    weapons.sort_by_key(|(_, equipped, throwable)| (!equipped, !throwable));
+    weapons
+        .into_iter()
+        .find(|(_, equipped, throwable)| *equipped || *throwable)
+        .map(|(entity, _, _)| entity)
+}
+
+/// The tiles a shot passes through on its way to a target.
+#[derive(Clone, Debug, PartialEq)]
+pub struct LineOfFire {
+    /// Every tile from the one beside the shooter up to and including the target.
+    pub path: Vec<Point>,
+    /// The first tile the shot can't get past, if it stops short of the target.
+    pub blocked_at: Option<Point>,
+}
+
+impl LineOfFire {
+    /// Walls, closed doors, any tile `is_blocked` says is occupied and the end of the weapon's
+    /// `range` all stop a shot.
+    pub fn new(
+        map: &Map,
+        from: Point,
+        to: Point,
+        range: i32,
+        is_blocked: impl Fn(Point) -> bool,
+    ) -> Self {
+        let path: Vec<Point> = line2d(LineAlg::Bresenham, from, to)
+            .into_iter()
+            .filter(|pos| *pos != from)
+            .collect();
+        let blocked_at = path
+            .iter()
+            .enumerate()
+            .find(|(i, pos)| {
+                let solid = !matches!(
+                    map.try_tile(**pos),
+                    Some(
+                        TileType::Floor | TileType::DoorOpen | TileType::Exit | TileType::UpStairs
+                    )
+                );
+                *i as i32 >= range || solid || (**pos != to && is_blocked(**pos))
+            })
+            .map(|(_, pos)| *pos);
+
+        Self { path, blocked_at }
+    }
+
+    pub fn is_clear(&self) -> bool {
+        self.blocked_at.is_none()
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn open_map() -> Map {
+        let mut map = Map::new(10, 10);
+        map.tiles
+            .iter_mut()
+            .for_each(|tile| *tile = TileType::Floor);
+        map
+    }
+
+    #[test]
+    fn test_clear_line_of_fire() {
+        let map = open_map();
+        let line = LineOfFire::new(&map, Point::new(1, 1), Point::new(4, 1), 5, |_| false);
+
+        assert_eq!(
+            line.path,
+            vec![Point::new(2, 1), Point::new(3, 1), Point::new(4, 1)]
+        );
+        assert!(line.is_clear());
+    }
+
+    #[test]
+    fn test_walls_creatures_and_range_block_shots() {
+        let mut map = open_map();
+        let (from, to) = (Point::new(1, 1), Point::new(5, 1));
+
+        let line = LineOfFire::new(&map, from, to, 6, |pos| pos == Point::new(3, 1));
+        assert_eq!(line.blocked_at, Some(Point::new(3, 1)));
+
+        let line = LineOfFire::new(&map, from, to, 3, |_| false);
+        assert_eq!(line.blocked_at, Some(Point::new(5, 1)));
+
+        let idx = map.idx(2, 1);
+        map.tiles[idx] = TileType::DoorClosed;
+        let line = LineOfFire::new(&map, from, to, 6, |_| false);
+        assert_eq!(line.blocked_at, Some(Point::new(2, 1)));
+    }
+
+    #[test]
+    fn test_cycling_targets() {
+        let mut world = World::default();
+        let mut fov = FieldOfView::new(8);
+        let far = world.push((Enemy, Point::new(6, 0)));
+        let near = world.push((Enemy, Point::new(2, 0)));
+        let hidden = world.push((Enemy, Point::new(1, 0)));
+        fov.visible_tiles
+            .extend([Point::new(6, 0), Point::new(2, 0)]);
+
+        let targets = visible_targets(&world, Point::zero(), &fov);
+        assert_eq!(
+            targets,
+            vec![(near, Point::new(2, 0)), (far, Point::new(6, 0))]
+        );
+        assert!(!targets.iter().any(|(entity, _)| *entity == hidden));
+
+        let mut targeting = Targeting::default();
+        targeting.open(far, near);
+        targeting.cycle(&targets);
+        assert_eq!(targeting.target, Some(far));
+        targeting.cycle(&targets);
+        assert_eq!(targeting.target, Some(near));
+    }
+
+    #[test]
+    fn test_equipped_weapons_are_preferred_to_throwables() {
+        let mut world = World::default();
+        let player = spawn_player(&mut world, Point::zero());
+        let knives = world.push((
+            Item,
+            Carried(player),
+            Ranged { range: 5 },
+            Damage(Dice::flat(1)),
+        ));
+        world.push((
+            Item,
+            Carried(player),
+            Ranged { range: 6 },
+            Provides(vec![ProvidedEffect::new("Fireball", 4)]),
+        ));
+        let bow = world.push((
+            Item,
+            Carried(player),
+            Ranged { range: 6 },
+            Damage(Dice::flat(1)),
+            Equippable(EquipmentSlot::Ranged),
+        ));
+        assert_eq!(ranged_weapon(&world, player), Some(knives));
+
+        world
+            .entry(bow)
+            .unwrap()
+            .add_component(Equipped(EquipmentSlot::Ranged));
+        assert_eq!(ranged_weapon(&world, player), Some(bow));
+    }
+}
@@ -1,9 +1,13 @@
-#[derive(Clone, Copy, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
 pub enum TurnState {
+    MainMenu,
     AwaitingInput,
     PlayerTurn,
     MonsterTurn,
     GameOver,
     Victory,
     NextLevel,
+    PreviousLevel,
 }